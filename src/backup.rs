@@ -0,0 +1,188 @@
+use anyhow::{anyhow, Result as AnyResult};
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_BACKUP_COUNT: usize = 5;
+const BACKUP_EXTENSION: &str = "bak";
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S-%3f";
+
+fn with_suffix(filepath: &Path, suffix: &str) -> PathBuf {
+    let mut name = filepath.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Copies `filepath` to the first free `<filepath>.1`, `<filepath>.2`, ... name.
+pub fn make_backup(filepath: &Path) -> AnyResult<PathBuf> {
+    let mut counter = 1;
+    let backup_path = loop {
+        let backup_path = with_suffix(filepath, &format!(".{}", counter));
+        if !backup_path.exists() {
+            break backup_path;
+        }
+        counter += 1;
+    };
+    std::fs::copy(filepath, &backup_path)?;
+    Ok(backup_path)
+}
+
+/// Returns the existing timestamped backups of `filepath`, oldest first.
+pub fn list_timestamped_backups(filepath: &Path) -> AnyResult<Vec<PathBuf>> {
+    let folder = match filepath.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let file_name = filepath
+        .file_name()
+        .ok_or_else(|| anyhow!("'{:?}' has no file name", filepath))?
+        .to_string_lossy()
+        .into_owned();
+    let prefix = format!("{}.", file_name);
+    let suffix = format!(".{}", BACKUP_EXTENSION);
+
+    let mut backups = Vec::with_capacity(10);
+    for entry in std::fs::read_dir(folder)?.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with(&prefix) && name.ends_with(&suffix) {
+            backups.push(entry.path());
+        }
+    }
+    // The timestamps are zero padded, so name order is age order.
+    backups.sort();
+    Ok(backups)
+}
+
+/// Copies `filepath` to `<filepath>.<timestamp>.bak` and deletes the oldest of those
+/// backups so at most `keep` remain. A `keep` of 0 disables backups.
+pub fn make_timestamped_backup(filepath: &Path, keep: usize) -> AnyResult<Option<PathBuf>> {
+    if keep == 0 || !filepath.exists() {
+        return Ok(None);
+    }
+    let timestamp = chrono::Local::now().format(TIMESTAMP_FORMAT).to_string();
+    let backup_path = with_suffix(filepath, &format!(".{}.{}", timestamp, BACKUP_EXTENSION));
+    std::fs::copy(filepath, &backup_path)?;
+
+    let backups = list_timestamped_backups(filepath)?;
+    if backups.len() > keep {
+        for old_backup in &backups[..backups.len() - keep] {
+            std::fs::remove_file(old_backup)?;
+        }
+    }
+    Ok(Some(backup_path))
+}
+
+/// Replaces `filepath` with `contents` so that it either keeps its old contents or
+/// gets the new ones in full: the data goes to a synced temporary file in the same
+/// folder first, which is then renamed over the original.
+pub fn write_atomic(filepath: &Path, contents: &[u8]) -> AnyResult<()> {
+    let temp_path = with_suffix(filepath, &format!(".{}.tmp", std::process::id()));
+    let result = (|| -> AnyResult<()> {
+        let mut temp_file = std::fs::File::create(&temp_path)?;
+        temp_file.write_all(contents)?;
+        temp_file.sync_all()?;
+        // The replacement keeps the mode of the file it replaces.
+        if let Ok(metadata) = std::fs::metadata(filepath) {
+            temp_file.set_permissions(metadata.permissions())?;
+        }
+        drop(temp_file);
+        std::fs::rename(&temp_path, filepath)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result?;
+
+    // Persist the rename itself; not every platform allows opening folders.
+    if let Some(folder) = filepath.parent() {
+        if let Ok(folder) = std::fs::File::open(folder) {
+            let _ = folder.sync_all();
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_folder(name: &str) -> PathBuf {
+        let folder =
+            std::env::temp_dir().join(format!("stsse-backup-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&folder);
+        std::fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    #[test]
+    fn timestamped_backups_keep_the_newest() {
+        let folder = temp_folder("rotation");
+        let save = folder.join("IRONCLAD.autosave");
+        std::fs::write(&save, b"current").unwrap();
+        for day in 1..=6 {
+            let name = format!("IRONCLAD.autosave.200001{:02}-000000-000.bak", day);
+            std::fs::write(folder.join(name), b"old").unwrap();
+        }
+        std::fs::write(
+            folder.join("DEFECT.autosave.20000101-000000-000.bak"),
+            b"other",
+        )
+        .unwrap();
+
+        let backup = make_timestamped_backup(&save, DEFAULT_BACKUP_COUNT)
+            .unwrap()
+            .unwrap();
+
+        let backups = list_timestamped_backups(&save).unwrap();
+        let names: Vec<String> = backups
+            .iter()
+            .map(|x| x.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(backups.len(), DEFAULT_BACKUP_COUNT);
+        assert_eq!(names[0], "IRONCLAD.autosave.20000103-000000-000.bak");
+        assert_eq!(backups.last(), Some(&backup));
+        assert_eq!(std::fs::read(&backup).unwrap(), b"current");
+        assert!(folder
+            .join("DEFECT.autosave.20000101-000000-000.bak")
+            .exists());
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn no_backups_are_kept_for_zero() {
+        let folder = temp_folder("disabled");
+        let save = folder.join("IRONCLAD.autosave");
+        std::fs::write(&save, b"current").unwrap();
+        assert!(make_timestamped_backup(&save, 0).unwrap().is_none());
+        assert!(list_timestamped_backups(&save).unwrap().is_empty());
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn write_atomic_replaces_the_contents() {
+        let folder = temp_folder("atomic");
+        let save = folder.join("IRONCLAD.autosave");
+        write_atomic(&save, b"first").unwrap();
+        write_atomic(&save, b"second").unwrap();
+        assert_eq!(std::fs::read(&save).unwrap(), b"second");
+        let files: Vec<_> = std::fs::read_dir(&folder).unwrap().collect();
+        assert_eq!(files.len(), 1);
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_atomic_keeps_the_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let folder = temp_folder("permissions");
+        let save = folder.join("IRONCLAD.autosave");
+        std::fs::write(&save, b"first").unwrap();
+        std::fs::set_permissions(&save, std::fs::Permissions::from_mode(0o640)).unwrap();
+        write_atomic(&save, b"second").unwrap();
+        let mode = std::fs::metadata(&save).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+}
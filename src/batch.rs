@@ -0,0 +1,116 @@
+use anyhow::{Context, Result as AnyResult};
+
+use std::path::{Path, PathBuf};
+
+use crate::backup::{make_backup, write_atomic};
+use crate::codec::Codec;
+
+fn has_extension(filepath: &Path, extension: &str) -> bool {
+    filepath
+        .file_name()
+        .map(|x| x.to_string_lossy().to_lowercase().ends_with(extension))
+        .unwrap_or(false)
+}
+
+fn is_save_file(filepath: &Path) -> bool {
+    has_extension(filepath, ".autosave")
+}
+
+fn is_json_file(filepath: &Path) -> bool {
+    has_extension(filepath, ".autosave.json")
+}
+
+fn get_json_filename(filepath: &Path) -> PathBuf {
+    let mut json_name = filepath.as_os_str().to_owned();
+    json_name.push(".json");
+    PathBuf::from(json_name)
+}
+
+fn get_save_filename(filepath: &Path) -> PathBuf {
+    filepath.with_extension("")
+}
+
+fn get_filenames(folder: &Path) -> AnyResult<Vec<PathBuf>> {
+    let mut files = Vec::with_capacity(10);
+    for entry in std::fs::read_dir(folder)?.flatten() {
+        let filepath = entry.path();
+        if filepath.is_file() && (is_save_file(&filepath) || is_json_file(&filepath)) {
+            files.push(filepath);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn display_name(filepath: &Path) -> String {
+    filepath
+        .file_name()
+        .map(|x| x.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Decrypts every save in `folder` that has no `.json` sidecar yet into one,
+/// keeping a numbered backup of the save.
+pub fn decrypt_dir(folder: &Path, codec: &Codec) -> AnyResult<()> {
+    for filepath in get_filenames(folder)? {
+        if !is_save_file(&filepath) || get_json_filename(&filepath).exists() {
+            continue;
+        }
+        println!("Decrypting {}...", display_name(&filepath));
+        make_backup(&filepath)?;
+        let contents = std::fs::read(&filepath)?;
+        let json_text = codec
+            .decrypt(&contents)
+            .with_context(|| format!("Failed to decrypt '{:?}'", filepath))?;
+        std::fs::write(get_json_filename(&filepath), json_text)?;
+    }
+    Ok(())
+}
+
+/// Encrypts every `.autosave.json` sidecar in `folder` back over its save and deletes the sidecar.
+pub fn encrypt_dir(folder: &Path, codec: &Codec) -> AnyResult<()> {
+    for filepath in get_filenames(folder)? {
+        if !is_json_file(&filepath) {
+            continue;
+        }
+        println!("Encrypting {}...", display_name(&filepath));
+        let json_text = std::fs::read(&filepath)?;
+        write_atomic(&get_save_filename(&filepath), &codec.encrypt(&json_text))?;
+        std::fs::remove_file(&filepath)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_folder(name: &str) -> PathBuf {
+        let folder =
+            std::env::temp_dir().join(format!("stsse-batch-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&folder);
+        std::fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    #[test]
+    fn encrypt_dir_only_touches_save_sidecars() {
+        let folder = temp_folder("encrypt");
+        let codec = Codec::default();
+        let json_text = br#"{"gold":99}"#;
+        std::fs::write(folder.join("IRONCLAD.autosave.json"), json_text).unwrap();
+        std::fs::write(folder.join("settings.json"), b"{}").unwrap();
+        std::fs::write(folder.join("notes.txt.json"), b"[]").unwrap();
+
+        encrypt_dir(&folder, &codec).unwrap();
+
+        let save = std::fs::read(folder.join("IRONCLAD.autosave")).unwrap();
+        assert_eq!(codec.decrypt(&save).unwrap(), json_text.to_vec());
+        assert!(!folder.join("IRONCLAD.autosave.json").exists());
+        assert_eq!(std::fs::read(folder.join("settings.json")).unwrap(), b"{}");
+        assert_eq!(std::fs::read(folder.join("notes.txt.json")).unwrap(), b"[]");
+        assert!(!folder.join("settings").exists());
+        assert!(!folder.join("notes.txt").exists());
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -1,13 +1,15 @@
-use anyhow::{anyhow, Result as AnyResult};
+use anyhow::{anyhow, Context, Result as AnyResult};
 use bincode::serialize;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::io::{BufReader, BufWriter, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use crate::cache_enums::{CardColor, CardRarity, CardType, RelicTier};
+use crate::cache_enums::{CardColor, CardRarity, CardType, PlayerClass, PotionRarity, RelicTier};
+use crate::matching::Candidate;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Card {
@@ -15,25 +17,45 @@ pub struct Card {
     pub color: CardColor,
     pub type_: CardType,
     pub misc: u32,
+    /// Whether the card keeps state in `misc`, like Genetic Algorithm's block.
+    pub uses_misc: bool,
+    /// How many times the card can be upgraded, `None` for cards like Searing Blow that
+    /// can be upgraded without limit.
+    pub max_upgrades: Option<u32>,
     pub id: String,
+    /// The name the game shows, from its English localization files when available.
+    pub name: Option<String>,
 }
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Relic {
     pub tier: RelicTier,
     pub id: String,
+    /// The name the game shows, from its English localization files when available.
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Potion {
+    pub rarity: PotionRarity,
+    /// The only character that finds the potion, `None` for potions everyone finds.
+    pub class: Option<PlayerClass>,
+    pub id: String,
+    /// The name the game shows, from its English localization files when available.
+    pub name: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct STSCache {
     pub cards: Vec<Card>,
     pub relics: Vec<Relic>,
+    pub potions: Vec<Potion>,
 }
 
 fn parse_card(contents: &str) -> Card {
     let id_regex = Regex::new(r#"ID[ ]*=[ ]*"(.+)""#).expect("Failed to compile id regex.");
     let cap_regex = Regex::new(r"([A-Z]{2,})").expect("Failed to compile capital regex.");
     let id_match = id_regex
-        .captures(&contents)
+        .captures(contents)
         .expect("Failed to get create regex capture groups.");
     let id = id_match.get(1).expect("Failed to get id regex group.");
     let misc_regex = Regex::new(r#"misc[ ]*=[ ]*(\d+)"#).expect("Failed to compile misc regex.");
@@ -49,8 +71,17 @@ fn parse_card(contents: &str) -> Card {
         None => 0,
     };
 
+    // Cards upgrade once unless they override canUpgrade(), like Searing Blow does.
+    let can_upgrade_regex = Regex::new(r"boolean canUpgrade\(\)\s*\{\s*return (true|false);")
+        .expect("Failed to compile canUpgrade regex.");
+    let max_upgrades = match can_upgrade_regex.captures(contents).and_then(|c| c.get(1)) {
+        Some(m) if m.as_str() == "true" => None,
+        Some(_) => Some(0),
+        None => Some(1),
+    };
+
     let cap_matches: Vec<_> = cap_regex
-        .find_iter(&contents)
+        .find_iter(contents)
         .map(|mat| mat.as_str())
         .collect();
 
@@ -68,7 +99,10 @@ fn parse_card(contents: &str) -> Card {
         .collect();
     Card {
         misc,
+        uses_misc: contents.contains("this.misc"),
+        max_upgrades,
         id: id.as_str().to_owned(),
+        name: None,
         rarity: rarity
             .pop()
             .expect("Expected 1 rarity regex result, got 0."),
@@ -80,11 +114,11 @@ fn parse_card(contents: &str) -> Card {
 fn parse_relic(contents: &str) -> Option<Relic> {
     let id_regex = Regex::new(r#"ID[ ]*=[ ]*"(.+)""#).unwrap();
     let cap_regex = Regex::new(r"([A-Z]{2,})").unwrap();
-    let id_match = id_regex.captures(&contents).unwrap();
+    let id_match = id_regex.captures(contents).unwrap();
     let id = id_match.get(1)?;
 
     let cap_matches: Vec<_> = cap_regex
-        .find_iter(&contents)
+        .find_iter(contents)
         .map(|mat| mat.as_str())
         .collect();
 
@@ -98,24 +132,83 @@ fn parse_relic(contents: &str) -> Option<Relic> {
         Some(Relic {
             id: id.as_str().to_owned(),
             tier: rarity.pop().unwrap(),
+            name: None,
         })
     }
 }
 
+fn parse_potion(contents: &str) -> Option<Potion> {
+    let id_regex = Regex::new(r#"POTION_ID[ ]*=[ ]*"(.+)""#).unwrap();
+    let rarity_regex = Regex::new(r"PotionRarity\.([A-Z]+)").unwrap();
+    // Potions of a single character are outlined in that character's color in the compendium.
+    let class_regex =
+        Regex::new(r"labOutlineColor[ ]*=[ ]*Settings\.([A-Z]+)_RELIC_COLOR").unwrap();
+    let id = id_regex.captures(contents)?.get(1)?;
+    let rarity = rarity_regex
+        .captures(contents)
+        .and_then(|x| PotionRarity::from_str(x.get(1)?.as_str()))?;
+    let class = class_regex
+        .captures(contents)
+        .and_then(|x| PlayerClass::from_relic_color(x.get(1)?.as_str()));
+    Some(Potion {
+        rarity,
+        class,
+        id: id.as_str().to_owned(),
+        name: None,
+    })
+}
+
+/// Reads the display names out of one of the game's localization files, such as
+/// `localization/eng/cards.json` from the extracted game jar. Missing files give no names.
+fn load_names(src_folder: &Path, file_name: &str) -> AnyResult<HashMap<String, String>> {
+    let names_path = src_folder
+        .join("localization")
+        .join(STSCache::LOCALIZATION_LANGUAGE)
+        .join(file_name);
+    if !names_path.exists() {
+        return Ok(HashMap::new());
+    }
+    let contents = std::fs::read_to_string(&names_path)?;
+    let entries: HashMap<String, JsonValue> =
+        serde_json::from_str(contents.trim_start_matches('\u{feff}'))
+            .with_context(|| format!("Failed to parse localization file '{:?}'", names_path))?;
+    Ok(entries
+        .into_iter()
+        .filter_map(|(id, entry)| Some((id, entry.get("NAME")?.as_str()?.to_owned())))
+        .collect())
+}
+
+/// Pairs each of `ids` with its name from the `(id, name)` pairs of a cache list; ids the
+/// cache does not know, like modded ones, get no name.
+fn candidates<'a, 'b>(
+    ids: impl IntoIterator<Item = &'a String>,
+    known: impl IntoIterator<Item = (&'b String, &'b Option<String>)>,
+) -> Vec<Candidate> {
+    let names: HashMap<&String, &Option<String>> = known.into_iter().collect();
+    ids.into_iter()
+        .map(|id| Candidate {
+            id: id.clone(),
+            name: names.get(id).and_then(|x| (*x).clone()),
+        })
+        .collect()
+}
+
 impl std::fmt::Display for STSCache {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Cache ({} cards, {} relics)",
+            "Cache ({} cards, {} relics, {} potions)",
             self.cards.len(),
-            self.relics.len()
+            self.relics.len(),
+            self.potions.len()
         )
     }
 }
 
 impl STSCache {
     const CACHE_MAGIC_WORD: [u8; 4] = [0x5, 0xE, 0xE, 0x5];
-    const CACHE_VERSION: u32 = 1;
+    const CACHE_VERSION: u32 = 5;
+    const LOCALIZATION_LANGUAGE: &'static str = "eng";
     const CACHE_FILENAME: &'static str = "_cache.stsc";
 
     fn walk_dir(
@@ -128,35 +221,33 @@ impl STSCache {
         dir_queue.push(start_dir);
 
         while let Some(next_dir) = dir_queue.pop() {
-            for dir_contents in std::fs::read_dir(&next_dir)? {
-                if let Ok(entry) = dir_contents {
-                    if let Ok(file_data) = entry.file_type() {
-                        let filepath = entry.path();
-                        if file_data.is_file() {
-                            if !file_filter(&filepath) {
-                                continue;
-                            }
-                            if let Some(extension) = filepath.extension() {
-                                if extension.to_string_lossy().to_lowercase() == "java" {
-                                    if let Ok(file_contents) = std::fs::read_to_string(&filepath) {
-                                        results.push(file_contents);
-                                    }
+            for entry in std::fs::read_dir(&next_dir)?.flatten() {
+                if let Ok(file_data) = entry.file_type() {
+                    let filepath = entry.path();
+                    if file_data.is_file() {
+                        if !file_filter(&filepath) {
+                            continue;
+                        }
+                        if let Some(extension) = filepath.extension() {
+                            if extension.to_string_lossy().to_lowercase() == "java" {
+                                if let Ok(file_contents) = std::fs::read_to_string(&filepath) {
+                                    results.push(file_contents);
                                 }
                             }
-                        } else {
-                            if !folder_filter(&filepath) {
-                                continue;
-                            }
-                            dir_queue.push(filepath);
                         }
+                    } else {
+                        if !folder_filter(&filepath) {
+                            continue;
+                        }
+                        dir_queue.push(filepath);
                     }
-                };
+                }
             }
         }
         Ok(results)
     }
 
-    fn create_cache_from_src_folder(folder: &PathBuf) -> AnyResult<Self> {
+    fn create_cache_from_src_folder(folder: &Path) -> AnyResult<Self> {
         let src_folder = folder.join("sts_src");
         if src_folder.exists() {
             let cards = {
@@ -202,9 +293,14 @@ impl STSCache {
                     filename.is_some() && !files_to_skip.contains(filename.unwrap())
                 };
 
+                let names = load_names(&src_folder, "cards.json")?;
                 STSCache::walk_dir(card_folder, folder_filter, file_filter)?
                     .into_iter()
-                    .map(|x| parse_card(&x))
+                    .map(|x| {
+                        let mut card = parse_card(&x);
+                        card.name = names.get(&card.id).cloned();
+                        card
+                    })
                     .collect()
             };
 
@@ -244,20 +340,65 @@ impl STSCache {
                     filename.is_some() && !files_to_skip.contains(filename.unwrap())
                 };
 
+                let names = load_names(&src_folder, "relics.json")?;
                 STSCache::walk_dir(relic_folder, folder_filter, file_filter)?
                     .into_iter()
                     .filter_map(|x| parse_relic(&x))
+                    .map(|mut relic| {
+                        relic.name = names.get(&relic.id).cloned();
+                        relic
+                    })
                     .collect()
             };
-            let cache = STSCache { cards, relics };
-            cache.save(&folder);
+
+            let potions = {
+                let potion_folder = src_folder
+                    .join("com")
+                    .join("megacrit")
+                    .join("cardcrawl")
+                    .join("potions");
+                if !potion_folder.exists() {
+                    return Err(anyhow!(
+                        "Unable to find potions folder '{:?}'",
+                        potion_folder
+                    ));
+                }
+                let files_to_skip = {
+                    let mut temp = HashSet::with_capacity(20);
+                    temp.insert(std::ffi::OsStr::new("AbstractPotion.java"));
+                    // The placeholder the game keeps in empty slots, not a potion to give.
+                    temp.insert(std::ffi::OsStr::new("PotionSlot.java"));
+                    temp
+                };
+
+                let file_filter = |file: &PathBuf| {
+                    let filename = file.file_name();
+                    filename.is_some() && !files_to_skip.contains(filename.unwrap())
+                };
+
+                let names = load_names(&src_folder, "potions.json")?;
+                STSCache::walk_dir(potion_folder, |_| true, file_filter)?
+                    .into_iter()
+                    .filter_map(|x| parse_potion(&x))
+                    .map(|mut potion| {
+                        potion.name = names.get(&potion.id).cloned();
+                        potion
+                    })
+                    .collect()
+            };
+            let cache = STSCache {
+                cards,
+                relics,
+                potions,
+            };
+            cache.save(folder);
             Ok(cache)
         } else {
             Err(anyhow!("Unable to find src folder '{:?}'", src_folder))
         }
     }
 
-    fn load_cache(cache_filepath: &PathBuf) -> AnyResult<Self> {
+    fn load_cache(cache_filepath: &Path) -> AnyResult<Self> {
         let mut cache_file = BufReader::new(std::fs::File::open(cache_filepath)?);
 
         let magic_word = bincode::config().deserialize_from::<_, [u8; 4]>(&mut cache_file)?;
@@ -281,7 +422,7 @@ impl STSCache {
         Ok(bincode::config().deserialize_from::<_, STSCache>(&mut cache_file)?)
     }
 
-    fn save(&self, folder: &PathBuf) {
+    fn save(&self, folder: &Path) {
         let cache_filepath = folder.join(STSCache::CACHE_FILENAME);
         let mut cache_file = BufWriter::new(std::fs::File::create(cache_filepath).unwrap());
         if let Ok(serialized_data) = serialize(&STSCache::CACHE_MAGIC_WORD) {
@@ -295,11 +436,83 @@ impl STSCache {
         }
     }
 
-    pub fn load_or_create_from_file_in_folder(folder: &PathBuf) -> AnyResult<Self> {
+    /// Loads the cache saved in `folder`, never rebuilding or writing it.
+    pub fn load_from_folder(folder: &Path) -> AnyResult<Self> {
+        STSCache::load_cache(&folder.join(STSCache::CACHE_FILENAME))
+    }
+
+    pub fn load_or_create_from_file_in_folder(folder: &Path) -> AnyResult<Self> {
         if folder.join(STSCache::CACHE_FILENAME).exists() {
-            STSCache::load_cache(&folder.join(STSCache::CACHE_FILENAME))
+            match STSCache::load_cache(&folder.join(STSCache::CACHE_FILENAME)) {
+                // A cache from an older version is rebuilt if the sources are still around.
+                Err(error) if folder.join("sts_src").exists() => {
+                    eprintln!("Rebuilding the cache ({})", error);
+                    STSCache::create_cache_from_src_folder(folder)
+                }
+                result => result,
+            }
         } else {
-            STSCache::create_cache_from_src_folder(&folder)
+            STSCache::create_cache_from_src_folder(folder)
         }
     }
+
+    pub fn card_candidates<'a>(&self, ids: impl IntoIterator<Item = &'a String>) -> Vec<Candidate> {
+        candidates(ids, self.cards.iter().map(|x| (&x.id, &x.name)))
+    }
+
+    pub fn relic_candidates<'a>(
+        &self,
+        ids: impl IntoIterator<Item = &'a String>,
+    ) -> Vec<Candidate> {
+        candidates(ids, self.relics.iter().map(|x| (&x.id, &x.name)))
+    }
+
+    pub fn potion_candidates<'a>(
+        &self,
+        ids: impl IntoIterator<Item = &'a String>,
+    ) -> Vec<Candidate> {
+        candidates(ids, self.potions.iter().map(|x| (&x.id, &x.name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card_source(body: &str) -> String {
+        format!(
+            r#"public class Test extends AbstractCard {{
+    public static final String ID = "Test";
+    public Test() {{
+        super(ID, NAME, "img", 1, DESCRIPTION, AbstractCard.CardType.ATTACK, AbstractCard.CardColor.RED, AbstractCard.CardRarity.COMMON, AbstractCard.CardTarget.ENEMY);
+    }}
+{}
+}}"#,
+            body
+        )
+    }
+
+    #[test]
+    fn cards_upgrade_once_by_default() {
+        assert_eq!(parse_card(&card_source("")).max_upgrades, Some(1));
+    }
+
+    #[test]
+    fn overridden_can_upgrade_sets_the_limit() {
+        let unlimited =
+            "    @Override\n    public boolean canUpgrade() {\n        return true;\n    }";
+        assert_eq!(parse_card(&card_source(unlimited)).max_upgrades, None);
+        let never = "    public boolean canUpgrade() { return false; }";
+        assert_eq!(parse_card(&card_source(never)).max_upgrades, Some(0));
+    }
+
+    #[test]
+    fn cards_that_assign_misc_use_it() {
+        let card = parse_card(&card_source(
+            "    public void init() {\n        this.misc = 15;\n    }",
+        ));
+        assert!(card.uses_misc);
+        assert_eq!(card.misc, 15);
+        assert!(!parse_card(&card_source("")).uses_misc);
+    }
 }
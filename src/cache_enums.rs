@@ -1,6 +1,9 @@
+// Variant names mirror the Java enum constants they are parsed from.
+#![allow(clippy::upper_case_acronyms, clippy::should_implement_trait)]
+
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Copy)]
 pub enum CardRarity {
     BASIC,
     SPECIAL,
@@ -23,7 +26,7 @@ impl CardRarity {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Copy)]
 pub enum CardColor {
     RED,
     GREEN,
@@ -46,7 +49,7 @@ impl CardColor {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Copy)]
 pub enum CardType {
     ATTACK,
     SKILL,
@@ -66,7 +69,7 @@ impl CardType {
         }
     }
 }
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Copy)]
 pub enum RelicTier {
     DEPRECATED,
     STARTER,
@@ -92,3 +95,53 @@ impl RelicTier {
         }
     }
 }
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Copy)]
+pub enum PotionRarity {
+    PLACEHOLDER,
+    COMMON,
+    UNCOMMON,
+    RARE,
+}
+impl PotionRarity {
+    pub fn from_str(s: &str) -> Option<PotionRarity> {
+        match s {
+            "PLACEHOLDER" => Some(PotionRarity::PLACEHOLDER),
+            "COMMON" => Some(PotionRarity::COMMON),
+            "UNCOMMON" => Some(PotionRarity::UNCOMMON),
+            "RARE" => Some(PotionRarity::RARE),
+            _ => None,
+        }
+    }
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Copy)]
+pub enum PlayerClass {
+    IRONCLAD,
+    THE_SILENT,
+    DEFECT,
+    WATCHER,
+}
+impl PlayerClass {
+    pub fn from_str(s: &str) -> Option<PlayerClass> {
+        match s {
+            "IRONCLAD" => Some(PlayerClass::IRONCLAD),
+            "THE_SILENT" => Some(PlayerClass::THE_SILENT),
+            "DEFECT" => Some(PlayerClass::DEFECT),
+            "WATCHER" => Some(PlayerClass::WATCHER),
+            _ => None,
+        }
+    }
+
+    /// The class whose relics use the given `Settings.<COLOR>_RELIC_COLOR`.
+    pub fn from_relic_color(color: &str) -> Option<PlayerClass> {
+        match color {
+            "RED" => Some(PlayerClass::IRONCLAD),
+            "GREEN" => Some(PlayerClass::THE_SILENT),
+            "BLUE" => Some(PlayerClass::DEFECT),
+            "PURPLE" => Some(PlayerClass::WATCHER),
+            _ => None,
+        }
+    }
+}
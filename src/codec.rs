@@ -0,0 +1,808 @@
+use anyhow::{anyhow, Context, Result as AnyResult};
+use serde_json::Value as JsonValue;
+
+use std::io::{Read, Write};
+
+pub const DEFAULT_KEY: &[u8] = b"key";
+const STREAM_CHUNK_SIZE: usize = 4096;
+const MAX_RECOVERED_KEY_LEN: usize = 64;
+const KNOWN_SAVE_FIELDS: [&str; 4] = ["gold", "cards", "relics", "current_health"];
+
+fn base64_config() -> base64::Config {
+    base64::Config::new(base64::CharacterSet::Standard, true)
+}
+
+/// XORs `data` in place, treating its first byte as byte number `offset` of the stream.
+fn xor_with_key(data: &mut [u8], key: &[u8], offset: usize) {
+    let key_len = key.len();
+    data.iter_mut()
+        .enumerate()
+        .for_each(|(i, v)| *v ^= key[(offset + i) % key_len]);
+}
+
+pub fn encode(data: &[u8], key: &[u8]) -> Vec<u8> {
+    let mut result = Vec::from(data);
+    xor_with_key(&mut result, key, 0);
+    result
+}
+
+pub fn decode(data: &[u8], key: &[u8]) -> Vec<u8> {
+    encode(data, key)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+
+    fn detect(text: &[u8]) -> Self {
+        match text.iter().position(|x| *x == b'\n') {
+            Some(position) if position > 0 && text[position - 1] == b'\r' => LineEnding::CrLf,
+            _ => LineEnding::Lf,
+        }
+    }
+
+    fn trailing(text: &[u8]) -> Option<Self> {
+        if text.ends_with(b"\r\n") {
+            Some(LineEnding::CrLf)
+        } else if text.ends_with(b"\n") {
+            Some(LineEnding::Lf)
+        } else {
+            None
+        }
+    }
+}
+
+/// How the JSON text is stored in the save file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaveEncoding {
+    /// Plain JSON, written by the game when save obfuscation is disabled.
+    Plain,
+    /// XORed JSON as a single line of base64.
+    Obfuscated {
+        trailing_newline: Option<LineEnding>,
+    },
+    /// XORed JSON as base64 split into lines of `line_length` characters.
+    Wrapped {
+        line_length: usize,
+        line_ending: LineEnding,
+        trailing_newline: bool,
+    },
+}
+
+impl std::fmt::Display for SaveEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveEncoding::Plain => write!(f, "plain JSON"),
+            SaveEncoding::Obfuscated { .. } => write!(f, "obfuscated"),
+            SaveEncoding::Wrapped { line_length, .. } => {
+                write!(f, "wrapped base64 ({} characters per line)", line_length)
+            }
+        }
+    }
+}
+
+impl SaveEncoding {
+    fn detect(contents: &[u8]) -> Self {
+        let first_char = contents.iter().find(|x| !x.is_ascii_whitespace());
+        if first_char == Some(&b'{') || first_char == Some(&b'[') {
+            return SaveEncoding::Plain;
+        }
+        let lines: Vec<_> = contents
+            .split(|x| *x == b'\n')
+            .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+            .filter(|line| !line.is_empty())
+            .collect();
+        if lines.len() > 1 {
+            SaveEncoding::Wrapped {
+                line_length: lines[0].len(),
+                line_ending: LineEnding::detect(contents),
+                trailing_newline: LineEnding::trailing(contents).is_some(),
+            }
+        } else {
+            SaveEncoding::Obfuscated {
+                trailing_newline: LineEnding::trailing(contents),
+            }
+        }
+    }
+
+    fn write_base64(&self, data: &[u8]) -> Vec<u8> {
+        let based = base64::encode_config(data, base64_config());
+        match self {
+            SaveEncoding::Plain => data.to_vec(),
+            SaveEncoding::Obfuscated { trailing_newline } => {
+                let mut result = based.into_bytes();
+                if let Some(line_ending) = trailing_newline {
+                    result.extend_from_slice(line_ending.as_str().as_bytes());
+                }
+                result
+            }
+            SaveEncoding::Wrapped {
+                line_length,
+                line_ending,
+                trailing_newline,
+            } => {
+                let mut result = based
+                    .as_bytes()
+                    .chunks((*line_length).max(1))
+                    .collect::<Vec<_>>()
+                    .join(line_ending.as_str().as_bytes());
+                if *trailing_newline {
+                    result.extend_from_slice(line_ending.as_str().as_bytes());
+                }
+                result
+            }
+        }
+    }
+}
+
+/// Layout of the JSON text inside a save, detected on load so it can be reproduced on save.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonStyle {
+    Compact,
+    Pretty {
+        indent: String,
+        line_ending: LineEnding,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveFormat {
+    pub encoding: SaveEncoding,
+    pub json_style: JsonStyle,
+    /// Gson escapes `<`, `>`, `&`, `=` and `'` inside strings unless html escaping was disabled.
+    pub escape_html: bool,
+    pub trailing_newline: Option<LineEnding>,
+}
+
+impl Default for SaveFormat {
+    fn default() -> Self {
+        SaveFormat {
+            encoding: SaveEncoding::Obfuscated {
+                trailing_newline: None,
+            },
+            json_style: JsonStyle::Compact,
+            escape_html: true,
+            trailing_newline: None,
+        }
+    }
+}
+
+const HTML_ESCAPES: [(char, &str); 7] = [
+    ('<', "\\u003c"),
+    ('>', "\\u003e"),
+    ('&', "\\u0026"),
+    ('=', "\\u003d"),
+    ('\'', "\\u0027"),
+    ('\u{2028}', "\\u2028"),
+    ('\u{2029}', "\\u2029"),
+];
+
+impl SaveFormat {
+    fn detect(encoding: SaveEncoding, json_text: &[u8]) -> Self {
+        let text = String::from_utf8_lossy(json_text);
+        let json_style = match text.find('\n') {
+            Some(newline) => {
+                let indent: String = text[newline + 1..]
+                    .chars()
+                    .take_while(|c| *c == ' ' || *c == '\t')
+                    .collect();
+                JsonStyle::Pretty {
+                    indent,
+                    line_ending: LineEnding::detect(json_text),
+                }
+            }
+            None => JsonStyle::Compact,
+        };
+        SaveFormat {
+            encoding,
+            json_style,
+            escape_html: !text.contains(|c| HTML_ESCAPES.iter().any(|(html, _)| *html == c)),
+            trailing_newline: LineEnding::trailing(json_text),
+        }
+    }
+
+    fn write_json(&self, value: &JsonValue) -> Vec<u8> {
+        let mut json_text = match &self.json_style {
+            JsonStyle::Compact => value.to_string(),
+            JsonStyle::Pretty {
+                indent,
+                line_ending,
+            } => {
+                let mut buffer = Vec::with_capacity(128);
+                let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+                let mut serializer = serde_json::Serializer::with_formatter(&mut buffer, formatter);
+                serde::Serialize::serialize(value, &mut serializer)
+                    .expect("Serializing a JsonValue into memory can not fail.");
+                let json_text =
+                    String::from_utf8(buffer).expect("serde_json produced invalid UTF-8.");
+                match line_ending {
+                    LineEnding::Lf => json_text,
+                    LineEnding::CrLf => json_text.replace('\n', "\r\n"),
+                }
+            }
+        };
+        // Outside of strings the serialized JSON never contains these characters,
+        // so replacing them on the whole text only touches string contents.
+        if self.escape_html {
+            for (html, escaped) in HTML_ESCAPES.iter() {
+                if json_text.contains(*html) {
+                    json_text = json_text.replace(*html, escaped);
+                }
+            }
+        }
+        if let Some(line_ending) = self.trailing_newline {
+            json_text.push_str(line_ending.as_str());
+        }
+        json_text.into_bytes()
+    }
+}
+
+/// The save codec for one XOR key.
+#[derive(Debug, Clone)]
+pub struct Codec {
+    key: Vec<u8>,
+}
+
+impl Default for Codec {
+    fn default() -> Self {
+        Codec {
+            key: DEFAULT_KEY.to_vec(),
+        }
+    }
+}
+
+impl Codec {
+    pub fn new(key: &[u8]) -> AnyResult<Self> {
+        if key.is_empty() {
+            return Err(anyhow!("The XOR key can not be empty."));
+        }
+        Ok(Codec { key: key.to_vec() })
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// Turns an obfuscated save into its raw JSON text without parsing it.
+    pub fn decrypt(&self, contents: &[u8]) -> AnyResult<Vec<u8>> {
+        Ok(decode(&unbase_save(contents)?, &self.key))
+    }
+
+    /// Obfuscates raw JSON text as a single line of base64.
+    pub fn encrypt(&self, json_text: &[u8]) -> Vec<u8> {
+        base64::encode_config(&encode(json_text, &self.key), base64_config()).into_bytes()
+    }
+
+    pub fn decode_save(&self, contents: &[u8]) -> AnyResult<(JsonValue, SaveFormat)> {
+        let encoding = SaveEncoding::detect(contents);
+        let json_text = match encoding {
+            SaveEncoding::Plain => contents.to_vec(),
+            _ => decode(&unbase_save(contents)?, &self.key),
+        };
+        let json_dict: JsonValue = match encoding {
+            SaveEncoding::Plain => serde_json::from_slice(&json_text)?,
+            _ => serde_json::from_slice(&json_text)
+                .context("The decoded save is not valid JSON, is the XOR key right?")?,
+        };
+        Ok((json_dict, SaveFormat::detect(encoding, &json_text)))
+    }
+
+    pub fn encode_save(&self, value: &JsonValue, format: &SaveFormat) -> Vec<u8> {
+        let json_text = format.write_json(value);
+        match format.encoding {
+            SaveEncoding::Plain => json_text,
+            _ => format.encoding.write_base64(&encode(&json_text, &self.key)),
+        }
+    }
+
+    /// Decodes and re-encodes `contents` without edits, returning the offset of the
+    /// first byte that differs from the original (or `None` if both are identical).
+    pub fn find_roundtrip_mismatch(&self, contents: &[u8]) -> AnyResult<Option<usize>> {
+        let (value, format) = self.decode_save(contents)?;
+        let reencoded = self.encode_save(&value, &format);
+        let mismatch = contents
+            .iter()
+            .zip(reencoded.iter())
+            .position(|(a, b)| a != b);
+        Ok(match mismatch {
+            Some(position) => Some(position),
+            None if contents.len() != reencoded.len() => Some(contents.len().min(reencoded.len())),
+            None => None,
+        })
+    }
+
+    pub fn reader<R: Read>(&self, inner: R) -> SaveReader<R> {
+        SaveReader::with_codec(inner, self)
+    }
+
+    pub fn writer<W: Write>(&self, inner: W) -> SaveWriter<W> {
+        SaveWriter::with_codec(inner, self)
+    }
+}
+
+fn unbase_save(contents: &[u8]) -> AnyResult<Vec<u8>> {
+    let stripped: Vec<u8> = contents
+        .iter()
+        .filter(|x| !x.is_ascii_whitespace())
+        .copied()
+        .collect();
+    Ok(base64::decode_config(&stripped, base64_config())?)
+}
+
+pub fn decode_save(contents: &[u8]) -> AnyResult<JsonValue> {
+    decode_save_with_format(contents).map(|(value, _)| value)
+}
+
+pub fn decode_save_with_format(contents: &[u8]) -> AnyResult<(JsonValue, SaveFormat)> {
+    Codec::default().decode_save(contents)
+}
+
+pub fn encode_save(value: &JsonValue) -> Vec<u8> {
+    encode_save_with_format(value, &SaveFormat::default())
+}
+
+pub fn encode_save_with_format(value: &JsonValue, format: &SaveFormat) -> Vec<u8> {
+    Codec::default().encode_save(value, format)
+}
+
+pub fn find_roundtrip_mismatch(contents: &[u8]) -> AnyResult<Option<usize>> {
+    Codec::default().find_roundtrip_mismatch(contents)
+}
+
+/// How plausible `byte` is as a character of save JSON.
+fn plaintext_score(byte: u8) -> i32 {
+    match byte {
+        b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'"' | b'_' | b':' | b',' | b' ' => 2,
+        b'\n' | b'\r' | b'\t' | 0x21..=0x7e => 1,
+        _ => -20,
+    }
+}
+
+/// Shortens `key` to its smallest repeating period.
+fn shortest_period(key: &[u8]) -> &[u8] {
+    (1..key.len())
+        .filter(|period| key.len().is_multiple_of(*period))
+        .find(|period| (0..key.len()).all(|i| key[i] == key[i % period]))
+        .map_or(key, |period| &key[..period])
+}
+
+fn is_plausible_save(json_text: &[u8]) -> bool {
+    match serde_json::from_slice::<JsonValue>(json_text) {
+        Ok(JsonValue::Object(fields)) => KNOWN_SAVE_FIELDS
+            .iter()
+            .any(|field| fields.contains_key(*field)),
+        _ => false,
+    }
+}
+
+/// Works out the repeating XOR key of an obfuscated save.
+///
+/// Each key byte is picked so that the bytes it decodes look most like JSON text,
+/// with the first one pinned by the leading `{` of the save object. A candidate key
+/// is only accepted if the whole save then decodes to an object with known fields.
+pub fn recover_key(contents: &[u8]) -> AnyResult<Vec<u8>> {
+    if SaveEncoding::detect(contents) == SaveEncoding::Plain {
+        return Err(anyhow!(
+            "The save is plain JSON, it is not XORed with any key."
+        ));
+    }
+    let data = unbase_save(contents)?;
+    if data.is_empty() {
+        return Err(anyhow!("The save is empty."));
+    }
+    for key_len in 1..=MAX_RECOVERED_KEY_LEN.min(data.len()) {
+        let key: Vec<u8> = (0..key_len)
+            .map(|column| {
+                if column == 0 {
+                    return data[0] ^ b'{';
+                }
+                (0..=255u8)
+                    .max_by_key(|candidate| {
+                        data.iter()
+                            .skip(column)
+                            .step_by(key_len)
+                            .map(|x| plaintext_score(x ^ candidate))
+                            .sum::<i32>()
+                    })
+                    .expect("The candidate range is never empty.")
+            })
+            .collect();
+        if is_plausible_save(&decode(&data, &key)) {
+            return Ok(shortest_period(&key).to_vec());
+        }
+    }
+    Err(anyhow!(
+        "Unable to recover a key of up to {} bytes.",
+        MAX_RECOVERED_KEY_LEN
+    ))
+}
+
+/// Decodes a save file while it is being read, yielding the plain JSON bytes.
+pub struct SaveReader<R: Read> {
+    inner: R,
+    key: Vec<u8>,
+    offset: usize,
+    encoded: Vec<u8>,
+    decoded: Vec<u8>,
+    decoded_pos: usize,
+    finished: bool,
+}
+
+impl<R: Read> SaveReader<R> {
+    pub fn new(inner: R) -> Self {
+        SaveReader::with_codec(inner, &Codec::default())
+    }
+
+    /// Uses the key of `codec`, which `Codec::new` has already checked is not empty.
+    pub fn with_codec(inner: R, codec: &Codec) -> Self {
+        SaveReader {
+            inner,
+            key: codec.key.clone(),
+            offset: 0,
+            encoded: Vec::with_capacity(STREAM_CHUNK_SIZE),
+            decoded: Vec::with_capacity(STREAM_CHUNK_SIZE),
+            decoded_pos: 0,
+            finished: false,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn fill_decoded(&mut self) -> std::io::Result<()> {
+        self.decoded.clear();
+        self.decoded_pos = 0;
+        let mut chunk = [0u8; STREAM_CHUNK_SIZE];
+        while self.decoded.is_empty() && !self.finished {
+            let read = match self.inner.read(&mut chunk) {
+                Ok(read) => read,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            // Line breaks of wrapped saves carry no data.
+            self.encoded.extend(
+                chunk[..read]
+                    .iter()
+                    .filter(|x| !x.is_ascii_whitespace())
+                    .copied(),
+            );
+            // Only whole 4-character groups can be decoded until the end of input.
+            let usable = if read == 0 {
+                self.finished = true;
+                self.encoded.len()
+            } else {
+                self.encoded.len() - self.encoded.len() % 4
+            };
+            if usable == 0 {
+                continue;
+            }
+            base64::decode_config_buf(&self.encoded[..usable], base64_config(), &mut self.decoded)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            self.encoded.drain(..usable);
+        }
+        xor_with_key(&mut self.decoded, &self.key, self.offset);
+        self.offset += self.decoded.len();
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for SaveReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.decoded_pos == self.decoded.len() {
+            self.fill_decoded()?;
+        }
+        let available = &self.decoded[self.decoded_pos..];
+        let amount = available.len().min(buf.len());
+        buf[..amount].copy_from_slice(&available[..amount]);
+        self.decoded_pos += amount;
+        Ok(amount)
+    }
+}
+
+/// Encodes plain JSON bytes into the save file format while they are being written.
+///
+/// `finish` must be called to write out the final base64 group and its padding.
+pub struct SaveWriter<W: Write> {
+    inner: W,
+    key: Vec<u8>,
+    offset: usize,
+    pending: Vec<u8>,
+    encoded: String,
+}
+
+impl<W: Write> SaveWriter<W> {
+    pub fn new(inner: W) -> Self {
+        SaveWriter::with_codec(inner, &Codec::default())
+    }
+
+    /// Uses the key of `codec`, which `Codec::new` has already checked is not empty.
+    pub fn with_codec(inner: W, codec: &Codec) -> Self {
+        SaveWriter {
+            inner,
+            key: codec.key.clone(),
+            offset: 0,
+            pending: Vec::with_capacity(STREAM_CHUNK_SIZE),
+            encoded: String::with_capacity(STREAM_CHUNK_SIZE),
+        }
+    }
+
+    fn write_encoded(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.encoded.clear();
+        base64::encode_config_buf(data, base64_config(), &mut self.encoded);
+        self.inner.write_all(self.encoded.as_bytes())
+    }
+
+    pub fn finish(mut self) -> std::io::Result<W> {
+        let pending = std::mem::take(&mut self.pending);
+        self.write_encoded(&pending)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for SaveWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let start = self.pending.len();
+        self.pending.extend_from_slice(buf);
+        xor_with_key(&mut self.pending[start..], &self.key, self.offset);
+        self.offset += buf.len();
+        // Only whole 3-byte groups can be encoded without emitting padding.
+        let usable = self.pending.len() - self.pending.len() % 3;
+        if usable >= STREAM_CHUNK_SIZE {
+            let ready: Vec<u8> = self.pending.drain(..usable).collect();
+            self.write_encoded(&ready)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let usable = self.pending.len() - self.pending.len() % 3;
+        if usable > 0 {
+            let ready: Vec<u8> = self.pending.drain(..usable).collect();
+            self.write_encoded(&ready)?;
+        }
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A save large enough to span several stream chunks.
+    fn sample_json(cards: usize) -> String {
+        let deck: Vec<String> = (0..cards)
+            .map(|i| format!(r#"{{"id":"Strike_R","upgrades":{},"misc":0}}"#, i % 2))
+            .collect();
+        format!(
+            r#"{{"gold":99,"current_health":68,"max_health":80,"cards":[{}],"relics":["Burning Blood"],"name":"Tester"}}"#,
+            deck.join(",")
+        )
+    }
+
+    /// Hands out the data a few bytes at a time, like a slow pipe.
+    struct Trickle<'a> {
+        data: &'a [u8],
+        step: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let amount = self.step.min(buf.len()).min(self.data.len());
+            buf[..amount].copy_from_slice(&self.data[..amount]);
+            self.data = &self.data[amount..];
+            Ok(amount)
+        }
+    }
+
+    #[test]
+    fn reader_keeps_the_key_offset_across_chunks() {
+        let json = sample_json(1000);
+        assert!(json.len() > 3 * STREAM_CHUNK_SIZE);
+        // A key length that does not divide the chunk size shifts the key in every chunk.
+        let codec = Codec::new(b"abcde").unwrap();
+        let encrypted = codec.encrypt(json.as_bytes());
+        for step in [1, 7, 4096, 5000] {
+            let mut decoded = Vec::new();
+            codec
+                .reader(Trickle {
+                    data: &encrypted,
+                    step,
+                })
+                .read_to_end(&mut decoded)
+                .unwrap();
+            assert_eq!(decoded, json.as_bytes(), "reading {} bytes at a time", step);
+        }
+    }
+
+    #[test]
+    fn reader_skips_line_breaks_of_wrapped_saves() {
+        let json = sample_json(500);
+        let codec = Codec::default();
+        let wrapped = SaveEncoding::Wrapped {
+            line_length: 76,
+            line_ending: LineEnding::CrLf,
+            trailing_newline: true,
+        }
+        .write_base64(&encode(json.as_bytes(), codec.key()));
+        let mut decoded = Vec::new();
+        codec
+            .reader(Trickle {
+                data: &wrapped,
+                step: 13,
+            })
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, json.as_bytes());
+    }
+
+    #[test]
+    fn writer_matches_encrypting_in_one_go() {
+        let json = sample_json(1000);
+        let codec = Codec::new(b"abcde").unwrap();
+        let mut writer = codec.writer(Vec::new());
+        for (i, piece) in json.as_bytes().chunks(1234).enumerate() {
+            writer.write_all(piece).unwrap();
+            if i % 2 == 0 {
+                writer.flush().unwrap();
+            }
+        }
+        let written = writer.finish().unwrap();
+        assert_eq!(written, codec.encrypt(json.as_bytes()));
+    }
+
+    #[test]
+    fn empty_keys_are_rejected() {
+        assert!(Codec::new(b"").is_err());
+    }
+
+    #[test]
+    fn writer_output_reads_back() {
+        let json = sample_json(10);
+        let mut writer = SaveWriter::new(Vec::new());
+        writer.write_all(json.as_bytes()).unwrap();
+        let written = writer.finish().unwrap();
+        let mut decoded = String::new();
+        SaveReader::new(written.as_slice())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, json);
+    }
+
+    fn assert_roundtrip(contents: &[u8]) {
+        let (value, format) = decode_save_with_format(contents).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&encode_save_with_format(&value, &format)),
+            String::from_utf8_lossy(contents)
+        );
+        assert_eq!(find_roundtrip_mismatch(contents).unwrap(), None);
+    }
+
+    #[test]
+    fn compact_obfuscated_save_roundtrips() {
+        // Key order, number text and Gson's html escapes all have to survive.
+        let json = r#"{"relics":["Burning Blood"],"gold":99,"seed":-1234567890123456789,"ratio":1.50,"name":"A = B & <C>","cards":[]}"#;
+        assert_roundtrip(&Codec::default().encrypt(json.as_bytes()));
+    }
+
+    #[test]
+    fn pretty_plain_save_roundtrips() {
+        let json = "{\n  \"gold\": 99,\n  \"relics\": [\n    \"Burning Blood\"\n  ],\n  \"name\": \"<raw>\",\n  \"cards\": []\n}\n";
+        assert_roundtrip(json.as_bytes());
+    }
+
+    #[test]
+    fn pretty_wrapped_save_roundtrips() {
+        let json = "{\r\n\t\"gold\": 99,\r\n\t\"relics\": [\r\n\t\t\"Burning Blood\"\r\n\t]\r\n}";
+        let wrapped = SaveEncoding::Wrapped {
+            line_length: 60,
+            line_ending: LineEnding::CrLf,
+            trailing_newline: false,
+        }
+        .write_base64(&encode(json.as_bytes(), DEFAULT_KEY));
+        assert_roundtrip(&wrapped);
+    }
+
+    #[test]
+    fn roundtrip_mismatch_points_at_the_first_difference() {
+        // serde_json does not reproduce the space after the colon of a compact save.
+        let json = br#"{"gold": 99}"#;
+        assert_eq!(find_roundtrip_mismatch(json).unwrap(), Some(8));
+    }
+
+    #[test]
+    fn detects_plain_saves() {
+        assert_eq!(SaveEncoding::detect(b"  {\"gold\":1}"), SaveEncoding::Plain);
+        assert_eq!(SaveEncoding::detect(b"[1]\n"), SaveEncoding::Plain);
+    }
+
+    #[test]
+    fn detects_single_line_saves_and_their_newline() {
+        assert_eq!(
+            SaveEncoding::detect(b"eyJnb2xkIjoxfQ=="),
+            SaveEncoding::Obfuscated {
+                trailing_newline: None
+            }
+        );
+        assert_eq!(
+            SaveEncoding::detect(b"eyJnb2xkIjoxfQ==\r\n"),
+            SaveEncoding::Obfuscated {
+                trailing_newline: Some(LineEnding::CrLf)
+            }
+        );
+    }
+
+    #[test]
+    fn detects_wrapped_saves() {
+        assert_eq!(
+            SaveEncoding::detect(b"abcdefgh\r\nabcdefgh\r\nabcd\r\n"),
+            SaveEncoding::Wrapped {
+                line_length: 8,
+                line_ending: LineEnding::CrLf,
+                trailing_newline: true,
+            }
+        );
+        assert_eq!(
+            SaveEncoding::detect(b"abcd\nab"),
+            SaveEncoding::Wrapped {
+                line_length: 4,
+                line_ending: LineEnding::Lf,
+                trailing_newline: false,
+            }
+        );
+    }
+
+    #[test]
+    fn detects_the_json_layout() {
+        let format = SaveFormat::detect(SaveEncoding::Plain, b"{\r\n\t\"a\": \"<b>\"\r\n}\n");
+        assert_eq!(
+            format.json_style,
+            JsonStyle::Pretty {
+                indent: "\t".to_owned(),
+                line_ending: LineEnding::CrLf,
+            }
+        );
+        assert!(!format.escape_html);
+        assert_eq!(format.trailing_newline, Some(LineEnding::Lf));
+
+        let format = SaveFormat::detect(SaveEncoding::Plain, br#"{"a":"\u003cb\u003e"}"#);
+        assert_eq!(format.json_style, JsonStyle::Compact);
+        assert!(format.escape_html);
+        assert_eq!(format.trailing_newline, None);
+    }
+
+    #[test]
+    fn recovers_the_default_key() {
+        let save = Codec::default().encrypt(sample_json(20).as_bytes());
+        assert_eq!(recover_key(&save).unwrap(), DEFAULT_KEY);
+    }
+
+    #[test]
+    fn recovers_longer_keys() {
+        let key = b"s3cr3t-k3y!";
+        let save = Codec::new(key).unwrap().encrypt(sample_json(50).as_bytes());
+        assert_eq!(recover_key(&save).unwrap(), key);
+    }
+
+    #[test]
+    fn refuses_to_recover_a_key_of_plain_saves() {
+        assert!(recover_key(sample_json(1).as_bytes()).is_err());
+    }
+
+    #[test]
+    fn shortens_keys_to_their_period() {
+        assert_eq!(shortest_period(b"keykeykey"), b"key");
+        assert_eq!(shortest_period(b"abcab"), b"abcab");
+    }
+}
@@ -0,0 +1,353 @@
+use anyhow::Result as AnyResult;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+
+use std::collections::BTreeMap;
+
+use crate::cache::STSCache;
+use crate::cache_enums::{CardColor, CardRarity, CardType, RelicTier};
+use crate::save_file::SaveFile;
+
+/// Fields that get their own section of the diff instead of being listed as scalars.
+const SUMMARIZED_FIELDS: [&str; 4] = ["gold", "current_health", "max_health", "relic_counters"];
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CardChange {
+    pub id: String,
+    pub upgrades: i32,
+    pub count: usize,
+    /// `None` when the id is not in `STSCache`.
+    pub color: Option<CardColor>,
+    pub rarity: Option<CardRarity>,
+    #[serde(rename = "type")]
+    pub type_: Option<CardType>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RelicChange {
+    pub id: String,
+    pub count: usize,
+    /// `None` when the id is not in `STSCache`.
+    pub tier: Option<RelicTier>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub field: String,
+    /// `None` when the field is missing from that save.
+    pub before: Option<JsonValue>,
+    pub after: Option<JsonValue>,
+}
+
+/// What changed between two saves, in game terms rather than JSON terms.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct SaveDiff {
+    pub cards_added: Vec<CardChange>,
+    pub cards_removed: Vec<CardChange>,
+    pub relics_gained: Vec<RelicChange>,
+    pub relics_lost: Vec<RelicChange>,
+    pub gold_delta: i32,
+    pub current_health_delta: i32,
+    pub max_health_delta: i32,
+    pub changed_fields: Vec<FieldChange>,
+}
+
+fn count_items<T: Ord + Clone>(items: impl Iterator<Item = T>) -> BTreeMap<T, usize> {
+    let mut counts = BTreeMap::new();
+    for item in items {
+        *counts.entry(item).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// Returns the items of `after` that `before` lacks, with how many more copies there are.
+fn multiset_added<T: Ord + Clone>(
+    before: &BTreeMap<T, usize>,
+    after: &BTreeMap<T, usize>,
+) -> Vec<(T, usize)> {
+    after
+        .iter()
+        .filter_map(|(item, count)| {
+            let old_count = before.get(item).copied().unwrap_or(0);
+            if *count > old_count {
+                Some((item.clone(), count - old_count))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+fn is_scalar(value: &JsonValue) -> bool {
+    !matches!(value, JsonValue::Array(_) | JsonValue::Object(_))
+}
+
+fn card_changes(
+    cache: &STSCache,
+    before: &BTreeMap<(String, i32), usize>,
+    after: &BTreeMap<(String, i32), usize>,
+) -> Vec<CardChange> {
+    multiset_added(before, after)
+        .into_iter()
+        .map(|((id, upgrades), count)| {
+            let card = cache.cards.iter().find(|x| x.id == id);
+            CardChange {
+                color: card.map(|x| x.color),
+                rarity: card.map(|x| x.rarity),
+                type_: card.map(|x| x.type_),
+                id,
+                upgrades,
+                count,
+            }
+        })
+        .collect()
+}
+
+fn relic_changes(
+    cache: &STSCache,
+    before: &BTreeMap<String, usize>,
+    after: &BTreeMap<String, usize>,
+) -> Vec<RelicChange> {
+    multiset_added(before, after)
+        .into_iter()
+        .map(|(id, count)| RelicChange {
+            tier: cache.relics.iter().find(|x| x.id == id).map(|x| x.tier),
+            id,
+            count,
+        })
+        .collect()
+}
+
+impl SaveDiff {
+    pub fn between(before: &SaveFile, after: &SaveFile, cache: &STSCache) -> AnyResult<Self> {
+        let cards_before = count_items(before.cards.iter().map(|x| (x.id.clone(), x.upgrades)));
+        let cards_after = count_items(after.cards.iter().map(|x| (x.id.clone(), x.upgrades)));
+        let relics_before = count_items(before.relics.iter().cloned());
+        let relics_after = count_items(after.relics.iter().cloned());
+
+        let mut changed_fields = Vec::new();
+        if let (JsonValue::Object(fields_before), JsonValue::Object(fields_after)) =
+            (before.to_json()?, after.to_json()?)
+        {
+            let mut keys: Vec<&String> = fields_before.keys().collect();
+            keys.extend(
+                fields_after
+                    .keys()
+                    .filter(|x| !fields_before.contains_key(*x)),
+            );
+            for key in keys {
+                if SUMMARIZED_FIELDS.contains(&key.as_str()) {
+                    continue;
+                }
+                let value_before = fields_before.get(key);
+                let value_after = fields_after.get(key);
+                let all_scalar = value_before.into_iter().chain(value_after).all(is_scalar);
+                if all_scalar && value_before != value_after {
+                    changed_fields.push(FieldChange {
+                        field: key.clone(),
+                        before: value_before.cloned(),
+                        after: value_after.cloned(),
+                    });
+                }
+            }
+        }
+
+        Ok(SaveDiff {
+            cards_added: card_changes(cache, &cards_before, &cards_after),
+            cards_removed: card_changes(cache, &cards_after, &cards_before),
+            relics_gained: relic_changes(cache, &relics_before, &relics_after),
+            relics_lost: relic_changes(cache, &relics_after, &relics_before),
+            gold_delta: after.gold - before.gold,
+            current_health_delta: after.current_health - before.current_health,
+            max_health_delta: after.max_health - before.max_health,
+            changed_fields,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == SaveDiff::default()
+    }
+}
+
+impl std::fmt::Display for CardChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x {}", self.count, self.id)?;
+        if self.upgrades > 0 {
+            write!(f, "+{}", self.upgrades)?;
+        }
+        match (self.color, self.rarity, self.type_) {
+            (Some(color), Some(rarity), Some(type_)) => {
+                write!(f, " ({:?} {:?} {:?})", color, rarity, type_)
+            }
+            _ => write!(f, " (unknown card)"),
+        }
+    }
+}
+
+impl std::fmt::Display for RelicChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x {}", self.count, self.id)?;
+        match self.tier {
+            Some(tier) => write!(f, " ({:?})", tier),
+            None => write!(f, " (unknown relic)"),
+        }
+    }
+}
+
+fn format_field_value(value: &Option<JsonValue>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => "<missing>".to_owned(),
+    }
+}
+
+impl std::fmt::Display for SaveDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No changes.");
+        }
+        for card in &self.cards_added {
+            writeln!(f, "+ card  {}", card)?;
+        }
+        for card in &self.cards_removed {
+            writeln!(f, "- card  {}", card)?;
+        }
+        for relic in &self.relics_gained {
+            writeln!(f, "+ relic {}", relic)?;
+        }
+        for relic in &self.relics_lost {
+            writeln!(f, "- relic {}", relic)?;
+        }
+        if self.gold_delta != 0 {
+            writeln!(f, "  gold {:+}", self.gold_delta)?;
+        }
+        if self.current_health_delta != 0 {
+            writeln!(f, "  current_health {:+}", self.current_health_delta)?;
+        }
+        if self.max_health_delta != 0 {
+            writeln!(f, "  max_health {:+}", self.max_health_delta)?;
+        }
+        for change in &self.changed_fields {
+            writeln!(
+                f,
+                "  {}: {} -> {}",
+                change.field,
+                format_field_value(&change.before),
+                format_field_value(&change.after)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::{Card, Relic};
+    use serde_json::json;
+
+    fn test_cache() -> STSCache {
+        STSCache {
+            cards: vec![Card {
+                rarity: CardRarity::BASIC,
+                color: CardColor::RED,
+                type_: CardType::ATTACK,
+                misc: 0,
+                uses_misc: false,
+                max_upgrades: Some(1),
+                id: "Strike_R".to_owned(),
+                name: None,
+            }],
+            relics: vec![Relic {
+                tier: RelicTier::STARTER,
+                id: "Burning Blood".to_owned(),
+                name: None,
+            }],
+            potions: Vec::new(),
+        }
+    }
+
+    fn save(cards: &[(&str, i32)], relics: &[&str], gold: i32) -> SaveFile {
+        let cards: Vec<JsonValue> = cards
+            .iter()
+            .map(|(id, upgrades)| json!({"id": id, "upgrades": upgrades, "misc": 0}))
+            .collect();
+        SaveFile::from_json(json!({
+            "current_health": 60,
+            "max_health": 80,
+            "gold": gold,
+            "cards": cards,
+            "relics": relics,
+            "floor_num": 3,
+        }))
+        .unwrap()
+    }
+
+    fn card_change(id: &str, upgrades: i32, count: usize, known: bool) -> CardChange {
+        CardChange {
+            id: id.to_owned(),
+            upgrades,
+            count,
+            color: Some(CardColor::RED).filter(|_| known),
+            rarity: Some(CardRarity::BASIC).filter(|_| known),
+            type_: Some(CardType::ATTACK).filter(|_| known),
+        }
+    }
+
+    #[test]
+    fn identical_saves_have_no_diff() {
+        let before = save(&[("Strike_R", 0)], &["Burning Blood"], 99);
+        let diff = SaveDiff::between(&before, &before.clone(), &test_cache()).unwrap();
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "No changes.\n");
+    }
+
+    #[test]
+    fn duplicate_cards_are_counted() {
+        let before = save(
+            &[("Strike_R", 0), ("Strike_R", 0), ("Strike_R", 0)],
+            &[],
+            99,
+        );
+        let after = save(&[("Strike_R", 0), ("Bash", 0), ("Bash", 0)], &[], 99);
+        let diff = SaveDiff::between(&before, &after, &test_cache()).unwrap();
+        assert_eq!(diff.cards_added, [card_change("Bash", 0, 2, false)]);
+        assert_eq!(diff.cards_removed, [card_change("Strike_R", 0, 2, true)]);
+    }
+
+    #[test]
+    fn upgrades_show_as_a_swap() {
+        let before = save(&[("Strike_R", 0), ("Strike_R", 0)], &[], 99);
+        let after = save(&[("Strike_R", 0), ("Strike_R", 1)], &[], 99);
+        let diff = SaveDiff::between(&before, &after, &test_cache()).unwrap();
+        assert_eq!(diff.cards_added, [card_change("Strike_R", 1, 1, true)]);
+        assert_eq!(diff.cards_removed, [card_change("Strike_R", 0, 1, true)]);
+        assert_eq!(
+            diff.to_string(),
+            "+ card  1x Strike_R+1 (RED BASIC ATTACK)\n- card  1x Strike_R (RED BASIC ATTACK)\n"
+        );
+    }
+
+    #[test]
+    fn relics_and_scalars_are_compared() {
+        let before = save(&[], &["Burning Blood", "ModRelic", "ModRelic"], 99);
+        let mut after = save(&[], &["ModRelic"], 120);
+        after.floor_num = Some(4);
+        let diff = SaveDiff::between(&before, &after, &test_cache()).unwrap();
+        assert!(diff.relics_gained.is_empty());
+        let lost: Vec<String> = diff.relics_lost.iter().map(|x| x.to_string()).collect();
+        assert_eq!(
+            lost,
+            ["1x Burning Blood (STARTER)", "1x ModRelic (unknown relic)"]
+        );
+        assert_eq!(diff.gold_delta, 21);
+        assert_eq!(
+            diff.changed_fields,
+            [FieldChange {
+                field: "floor_num".to_owned(),
+                before: Some(json!(3)),
+                after: Some(json!(4)),
+            }]
+        );
+    }
+}
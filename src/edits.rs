@@ -0,0 +1,1085 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::cache::{Card, Relic, STSCache};
+use crate::cache_enums::{CardColor, PlayerClass, PotionRarity, RelicTier};
+use crate::error::SaveError;
+use crate::save_file::{CardSave, SaveFile, EMPTY_POTION_SLOT};
+
+fn default_count() -> usize {
+    1
+}
+
+/// A single change to a save; the interactive menu, the command line and edit scripts
+/// all go through these.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Edit {
+    AddGold {
+        amount: i32,
+    },
+    /// Sets current and/or max HP; `record` also rewrites this floor's HP metrics.
+    SetHealth {
+        current: Option<i32>,
+        max: Option<i32>,
+        #[serde(default)]
+        record: bool,
+    },
+    /// Changes max HP, then current HP, keeping current HP between 1 and max HP.
+    AddHealth {
+        #[serde(default)]
+        current: i32,
+        #[serde(default)]
+        max: i32,
+        #[serde(default)]
+        record: bool,
+    },
+    /// Heals to max HP.
+    Heal {
+        #[serde(default)]
+        record: bool,
+    },
+    AddCard {
+        id: String,
+        #[serde(default)]
+        upgrades: i32,
+        #[serde(default = "default_count")]
+        count: usize,
+    },
+    /// Removes `count` copies of the card, or every copy if `count` is `None`.
+    RemoveCard {
+        id: String,
+        count: Option<usize>,
+    },
+    RemoveAllCards,
+    /// Removes the card at `index` of the deck.
+    RemoveCardAt {
+        index: usize,
+    },
+    /// Upgrades the card at `index` of the deck once, as far as the card allows.
+    UpgradeCard {
+        index: usize,
+    },
+    /// Upgrades every card of the deck that can take another upgrade once.
+    UpgradeAllCards,
+    /// Takes one upgrade away from the card at `index` of the deck.
+    DowngradeCard {
+        index: usize,
+    },
+    /// Sets the `misc` value of the card at `index` of the deck, for cards that use it.
+    SetCardMisc {
+        index: usize,
+        misc: i32,
+    },
+    /// Adds `count` random cards, only of `color` if it is set.
+    AddRandomCards {
+        count: usize,
+        color: Option<CardColor>,
+    },
+    AddRelic {
+        id: String,
+    },
+    /// Removes the first copy of the relic together with its counter.
+    RemoveRelic {
+        id: String,
+    },
+    /// Adds `count` different random relics the save lacks, only of `tier` if it is set.
+    AddRandomRelics {
+        count: usize,
+        tier: Option<RelicTier>,
+    },
+    /// Puts the potion into slot `slot`, replacing whatever was there.
+    SetPotion {
+        slot: usize,
+        id: String,
+    },
+    /// Fills every empty potion slot with `id`, or with random potions the character can find.
+    FillPotions {
+        id: Option<String>,
+    },
+    /// Empties potion slot `slot`, or every slot if it is `None`.
+    ClearPotions {
+        slot: Option<usize>,
+    },
+    /// Changes the number of potion slots; slots that go away have to be empty.
+    SetPotionSlots {
+        count: usize,
+    },
+}
+
+impl std::fmt::Display for Edit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Edit::AddGold { amount } => write!(f, "Add {} gold", amount),
+            Edit::SetHealth {
+                current,
+                max,
+                record,
+            } => {
+                match (current, max) {
+                    (Some(current), Some(max)) => write!(f, "Set HP to {}/{}", current, max)?,
+                    (Some(current), None) => write!(f, "Set current HP to {}", current)?,
+                    (None, Some(max)) => write!(f, "Set max HP to {}", max)?,
+                    (None, None) => write!(f, "Keep HP")?,
+                }
+                write_record(f, *record)
+            }
+            Edit::AddHealth {
+                current,
+                max,
+                record,
+            } => {
+                match (*current, *max) {
+                    (current, 0) => write!(f, "Add {} HP", current)?,
+                    (0, max) => write!(f, "Add {} max HP", max)?,
+                    (current, max) => write!(f, "Add {} HP and {} max HP", current, max)?,
+                }
+                write_record(f, *record)
+            }
+            Edit::Heal { record } => {
+                write!(f, "Heal to full HP")?;
+                write_record(f, *record)
+            }
+            Edit::AddCard {
+                id,
+                upgrades,
+                count,
+            } => {
+                write!(f, "Add {}x card '{}'", count, id)?;
+                if *upgrades > 0 {
+                    write!(f, " with {} upgrade(s)", upgrades)?;
+                }
+                Ok(())
+            }
+            Edit::RemoveCard { id, count } => match count {
+                Some(count) => write!(f, "Remove {}x card '{}'", count, id),
+                None => write!(f, "Remove every card '{}'", id),
+            },
+            Edit::RemoveAllCards => write!(f, "Remove all cards"),
+            Edit::RemoveCardAt { index } => write!(f, "Remove card #{}", index),
+            Edit::UpgradeCard { index } => write!(f, "Upgrade card #{}", index),
+            Edit::UpgradeAllCards => write!(f, "Upgrade all cards"),
+            Edit::DowngradeCard { index } => write!(f, "Downgrade card #{}", index),
+            Edit::SetCardMisc { index, misc } => {
+                write!(f, "Set misc of card #{} to {}", index, misc)
+            }
+            Edit::AddRandomCards { count, color } => match color {
+                Some(color) => write!(f, "Add {} random {:?} cards", count, color),
+                None => write!(f, "Add {} random cards", count),
+            },
+            Edit::AddRelic { id } => write!(f, "Add relic '{}'", id),
+            Edit::RemoveRelic { id } => write!(f, "Remove relic '{}'", id),
+            Edit::AddRandomRelics { count, tier } => match tier {
+                Some(tier) => write!(f, "Add {} random {:?} relics", count, tier),
+                None => write!(f, "Add {} random relics", count),
+            },
+            Edit::SetPotion { slot, id } => write!(f, "Put potion '{}' in slot #{}", id, slot),
+            Edit::FillPotions { id } => match id {
+                Some(id) => write!(f, "Fill empty potion slots with '{}'", id),
+                None => write!(f, "Fill empty potion slots with random potions"),
+            },
+            Edit::ClearPotions { slot } => match slot {
+                Some(slot) => write!(f, "Empty potion slot #{}", slot),
+                None => write!(f, "Empty all potion slots"),
+            },
+            Edit::SetPotionSlots { count } => write!(f, "Set potion slots to {}", count),
+        }
+    }
+}
+
+fn write_record(f: &mut std::fmt::Formatter<'_>, record: bool) -> std::fmt::Result {
+    if record {
+        write!(f, " (recorded in the run history)")?;
+    }
+    Ok(())
+}
+
+fn new_card_save(card: &Card, upgrades: i32) -> CardSave {
+    CardSave {
+        id: card.id.clone(),
+        misc: card.misc as i32,
+        upgrades,
+    }
+}
+
+fn find_card<'a>(cache: &'a STSCache, id: &str) -> Result<&'a Card, SaveError> {
+    cache
+        .cards
+        .iter()
+        .find(|x| x.id == id)
+        .ok_or_else(|| SaveError::UnknownCardId { id: id.to_owned() })
+}
+
+fn add_gold(save: &mut SaveFile, amount: i32) -> Result<(), SaveError> {
+    // Only gold coming in counts towards the run's gold_gained statistic.
+    if amount > 0 {
+        let gold_gained = save
+            .gold_gained
+            .as_mut()
+            .ok_or_else(|| SaveError::missing("gold_gained"))?;
+        *gold_gained = checked_add("gold_gained", *gold_gained, amount)?;
+    }
+    save.gold = checked_add("gold", save.gold, amount)?.max(0);
+    Ok(())
+}
+
+fn check_health(current: Option<i32>, max: Option<i32>) -> Result<(), SaveError> {
+    if max.is_some_and(|x| x < 1) {
+        return Err(SaveError::invalid(
+            "max_health",
+            "max HP must be at least 1",
+        ));
+    }
+    if current.is_some_and(|x| x < 1) {
+        return Err(SaveError::invalid(
+            "current_health",
+            "current HP must be at least 1",
+        ));
+    }
+    if let (Some(current), Some(max)) = (current, max) {
+        if current > max {
+            return Err(SaveError::invalid(
+                "current_health",
+                &format!("current HP {} is above max HP {}", current, max),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Makes the HP the run history shows for the current floor match the save's HP.
+fn record_health(save: &mut SaveFile) -> Result<(), SaveError> {
+    let current_per_floor = save
+        .metric_current_hp_per_floor
+        .as_mut()
+        .ok_or_else(|| SaveError::missing("metric_current_hp_per_floor"))?;
+    if let Some(last) = current_per_floor.last_mut() {
+        *last = save.current_health;
+    }
+    let max_per_floor = save
+        .metric_max_hp_per_floor
+        .as_mut()
+        .ok_or_else(|| SaveError::missing("metric_max_hp_per_floor"))?;
+    if let Some(last) = max_per_floor.last_mut() {
+        *last = save.max_health;
+    }
+    Ok(())
+}
+
+fn set_health(
+    save: &mut SaveFile,
+    current: Option<i32>,
+    max: Option<i32>,
+    record: bool,
+) -> Result<(), SaveError> {
+    let max = max.unwrap_or(save.max_health);
+    // Lowering max HP drags current HP down with it, like the game does.
+    let current = current.unwrap_or_else(|| save.current_health.min(max));
+    check_health(Some(current), Some(max))?;
+    save.max_health = max;
+    save.current_health = current;
+    if record {
+        record_health(save)?;
+    }
+    Ok(())
+}
+
+/// Adds `amount` to the value of `field`, failing instead of overflowing.
+fn checked_add(field: &str, value: i32, amount: i32) -> Result<i32, SaveError> {
+    value.checked_add(amount).ok_or_else(|| {
+        SaveError::invalid(
+            field,
+            &format!("adding {} to {} is out of range", amount, value),
+        )
+    })
+}
+
+fn add_health(save: &mut SaveFile, current: i32, max: i32, record: bool) -> Result<(), SaveError> {
+    let max_health = checked_add("max_health", save.max_health, max)?.max(1);
+    let current_health = checked_add("current_health", save.current_health, current)?;
+    save.max_health = max_health;
+    save.current_health = current_health.clamp(1, max_health);
+    if record {
+        record_health(save)?;
+    }
+    Ok(())
+}
+
+fn remove_card(save: &mut SaveFile, id: &str, count: Option<usize>) -> Result<(), SaveError> {
+    let owned = save.cards.iter().filter(|x| x.id == id).count();
+    if owned == 0 {
+        return Err(SaveError::CardNotInDeck { id: id.to_owned() });
+    }
+    // Later copies go first, so the deck keeps its original order otherwise.
+    let mut to_remove = count.unwrap_or(owned).min(owned);
+    let mut index = save.cards.len();
+    while to_remove > 0 && index > 0 {
+        index -= 1;
+        if save.cards[index].id == id {
+            save.cards.remove(index);
+            to_remove -= 1;
+        }
+    }
+    Ok(())
+}
+
+fn card_at(save: &mut SaveFile, index: usize) -> Result<&mut CardSave, SaveError> {
+    save.cards
+        .get_mut(index)
+        .ok_or(SaveError::NoCardAtIndex { index })
+}
+
+fn check_upgrades(card: &Card, upgrades: i32) -> Result<(), SaveError> {
+    let too_many = match card.max_upgrades {
+        Some(max_upgrades) => upgrades > max_upgrades as i32,
+        None => false,
+    };
+    if upgrades < 0 || too_many {
+        return Err(SaveError::CannotUpgrade {
+            id: card.id.clone(),
+        });
+    }
+    Ok(())
+}
+
+fn upgrade_card(cache: &STSCache, save: &mut SaveFile, index: usize) -> Result<(), SaveError> {
+    let card = card_at(save, index)?;
+    check_upgrades(find_card(cache, &card.id)?, card.upgrades + 1)?;
+    card.upgrades += 1;
+    Ok(())
+}
+
+/// Cards the cache does not know, like curses, are left alone.
+fn upgrade_all_cards(cache: &STSCache, save: &mut SaveFile) {
+    for card in save.cards.iter_mut() {
+        let upgradable = find_card(cache, &card.id)
+            .and_then(|x| check_upgrades(x, card.upgrades + 1))
+            .is_ok();
+        if upgradable {
+            card.upgrades += 1;
+        }
+    }
+}
+
+fn downgrade_card(save: &mut SaveFile, index: usize) -> Result<(), SaveError> {
+    let card = card_at(save, index)?;
+    if card.upgrades <= 0 {
+        return Err(SaveError::NotUpgraded {
+            id: card.id.clone(),
+        });
+    }
+    card.upgrades -= 1;
+    Ok(())
+}
+
+fn set_card_misc(
+    cache: &STSCache,
+    save: &mut SaveFile,
+    index: usize,
+    misc: i32,
+) -> Result<(), SaveError> {
+    let card = card_at(save, index)?;
+    if !find_card(cache, &card.id)?.uses_misc {
+        return Err(SaveError::invalid(
+            "misc",
+            &format!("card '{}' does not use misc", card.id),
+        ));
+    }
+    card.misc = misc;
+    Ok(())
+}
+
+fn add_random_cards(
+    cache: &STSCache,
+    save: &mut SaveFile,
+    rng: &mut impl Rng,
+    count: usize,
+    filter: impl Fn(&Card) -> bool,
+) -> Result<(), SaveError> {
+    let candidates: Vec<&Card> = cache.cards.iter().filter(|x| filter(x)).collect();
+    if candidates.is_empty() {
+        return Err(SaveError::NoMatchingCards);
+    }
+    for _ in 0..count {
+        let random_card = candidates[rng.gen_range(0, candidates.len())];
+        save.cards.push(new_card_save(random_card, 0));
+    }
+    Ok(())
+}
+
+/// Gives `relic_counters` one entry per relic, the game indexes both lists together.
+/// Relics without a counter get -1, like the game writes for them.
+fn align_relic_counters(save: &mut SaveFile) {
+    if let Some(relic_counters) = save.relic_counters.as_mut() {
+        relic_counters.resize(save.relics.len(), -1);
+    }
+}
+
+fn push_relic(save: &mut SaveFile, id: &str) {
+    align_relic_counters(save);
+    save.relics.push(id.to_owned());
+    align_relic_counters(save);
+}
+
+fn add_relic(cache: &STSCache, save: &mut SaveFile, id: &str) -> Result<(), SaveError> {
+    if !cache.relics.iter().any(|x| x.id == id) {
+        return Err(SaveError::UnknownRelicId { id: id.to_owned() });
+    }
+    if save.relics.iter().any(|x| x == id) {
+        return Err(SaveError::RelicAlreadyOwned { id: id.to_owned() });
+    }
+    push_relic(save, id);
+    Ok(())
+}
+
+fn remove_relic(save: &mut SaveFile, id: &str) -> Result<(), SaveError> {
+    let index = save
+        .relics
+        .iter()
+        .position(|x| x == id)
+        .ok_or_else(|| SaveError::RelicNotOwned { id: id.to_owned() })?;
+    align_relic_counters(save);
+    save.relics.remove(index);
+    if let Some(relic_counters) = save.relic_counters.as_mut() {
+        relic_counters.remove(index);
+    }
+    Ok(())
+}
+
+fn add_random_relics(
+    cache: &STSCache,
+    save: &mut SaveFile,
+    rng: &mut impl Rng,
+    count: usize,
+    tier: Option<RelicTier>,
+) -> Result<(), SaveError> {
+    let candidates: Vec<&Relic> = cache
+        .relics
+        .iter()
+        .filter(|x| tier.is_none_or(|tier| x.tier == tier) && !save.relics.contains(&x.id))
+        .collect();
+    if candidates.is_empty() {
+        return Err(SaveError::NoMatchingRelics);
+    }
+    if candidates.len() < count {
+        return Err(SaveError::NotEnoughRelics {
+            requested: count,
+            available: candidates.len(),
+        });
+    }
+    let picked: Vec<&&Relic> = candidates.choose_multiple(rng, count).collect();
+    for relic in picked {
+        push_relic(save, &relic.id);
+    }
+    Ok(())
+}
+
+fn check_potion(cache: &STSCache, id: &str) -> Result<(), SaveError> {
+    if cache.potions.iter().any(|x| x.id == id) {
+        Ok(())
+    } else {
+        Err(SaveError::UnknownPotionId { id: id.to_owned() })
+    }
+}
+
+/// The potion slots of the save, with an empty slot for every slot `potion_slots` counts
+/// but `potions` lacks.
+fn potions_mut(save: &mut SaveFile) -> Result<&mut Vec<String>, SaveError> {
+    let slots = save
+        .potion_slots
+        .ok_or_else(|| SaveError::missing("potion_slots"))?;
+    let potions = save
+        .potions
+        .as_mut()
+        .ok_or_else(|| SaveError::missing("potions"))?;
+    let slots = slots.max(0) as usize;
+    if potions.len() < slots {
+        potions.resize(slots, EMPTY_POTION_SLOT.to_owned());
+    }
+    Ok(potions)
+}
+
+fn set_potion(
+    cache: &STSCache,
+    save: &mut SaveFile,
+    slot: usize,
+    id: &str,
+) -> Result<(), SaveError> {
+    check_potion(cache, id)?;
+    let potion = potions_mut(save)?
+        .get_mut(slot)
+        .ok_or(SaveError::NoPotionSlot { slot })?;
+    *potion = id.to_owned();
+    Ok(())
+}
+
+fn fill_potions(
+    cache: &STSCache,
+    save: &mut SaveFile,
+    rng: &mut impl Rng,
+    id: Option<&str>,
+) -> Result<(), SaveError> {
+    let class = save.loadout.as_deref().and_then(PlayerClass::from_str);
+    let candidates: Vec<&str> = match id {
+        Some(id) => {
+            check_potion(cache, id)?;
+            vec![id]
+        }
+        None => cache
+            .potions
+            .iter()
+            .filter(|x| x.rarity != PotionRarity::PLACEHOLDER)
+            .filter(|x| x.class.is_none() || x.class == class)
+            .map(|x| x.id.as_str())
+            .collect(),
+    };
+    if candidates.is_empty() {
+        return Err(SaveError::NoMatchingPotions);
+    }
+    for potion in potions_mut(save)?.iter_mut() {
+        if potion == EMPTY_POTION_SLOT {
+            *potion = candidates[rng.gen_range(0, candidates.len())].to_owned();
+        }
+    }
+    Ok(())
+}
+
+fn clear_potions(save: &mut SaveFile, slot: Option<usize>) -> Result<(), SaveError> {
+    let potions = potions_mut(save)?;
+    match slot {
+        Some(slot) => {
+            let potion = potions
+                .get_mut(slot)
+                .ok_or(SaveError::NoPotionSlot { slot })?;
+            *potion = EMPTY_POTION_SLOT.to_owned();
+        }
+        None => potions.fill(EMPTY_POTION_SLOT.to_owned()),
+    }
+    Ok(())
+}
+
+fn set_potion_slots(save: &mut SaveFile, count: usize) -> Result<(), SaveError> {
+    let potions = potions_mut(save)?;
+    if let Some((slot, potion)) = potions
+        .iter()
+        .enumerate()
+        .skip(count)
+        .find(|(_, x)| *x != EMPTY_POTION_SLOT)
+    {
+        return Err(SaveError::invalid(
+            "potion_slots",
+            &format!("slot #{} still holds '{}', empty it first", slot, potion),
+        ));
+    }
+    potions.resize(count, EMPTY_POTION_SLOT.to_owned());
+    save.potion_slots = Some(count as i32);
+    Ok(())
+}
+
+impl Edit {
+    /// Checks the ids the edit refers to against `STSCache` without touching any save.
+    pub fn validate(&self, cache: &STSCache) -> Result<(), SaveError> {
+        match self {
+            Edit::SetHealth { current, max, .. } => check_health(*current, *max),
+            Edit::AddCard { id, upgrades, .. } => check_upgrades(find_card(cache, id)?, *upgrades),
+            Edit::AddRelic { id } => {
+                if cache.relics.iter().any(|x| x.id == *id) {
+                    Ok(())
+                } else {
+                    Err(SaveError::UnknownRelicId { id: id.clone() })
+                }
+            }
+            Edit::SetPotion { id, .. } | Edit::FillPotions { id: Some(id) } => {
+                check_potion(cache, id)
+            }
+            Edit::AddRandomRelics {
+                tier: Some(tier), ..
+            } => {
+                if cache.relics.iter().any(|x| x.tier == *tier) {
+                    Ok(())
+                } else {
+                    Err(SaveError::NoMatchingRelics)
+                }
+            }
+            Edit::AddRandomCards {
+                color: Some(color), ..
+            } => {
+                if cache.cards.iter().any(|x| x.color == *color) {
+                    Ok(())
+                } else {
+                    Err(SaveError::NoMatchingCards)
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn apply(
+        &self,
+        save: &mut SaveFile,
+        cache: &STSCache,
+        rng: &mut impl Rng,
+    ) -> Result<(), SaveError> {
+        match self {
+            Edit::AddGold { amount } => add_gold(save, *amount),
+            Edit::SetHealth {
+                current,
+                max,
+                record,
+            } => set_health(save, *current, *max, *record),
+            Edit::AddHealth {
+                current,
+                max,
+                record,
+            } => add_health(save, *current, *max, *record),
+            Edit::Heal { record } => set_health(save, Some(save.max_health), None, *record),
+            Edit::AddCard {
+                id,
+                upgrades,
+                count,
+            } => {
+                let card = find_card(cache, id)?;
+                check_upgrades(card, *upgrades)?;
+                for _ in 0..*count {
+                    save.cards.push(new_card_save(card, *upgrades));
+                }
+                Ok(())
+            }
+            Edit::RemoveCard { id, count } => remove_card(save, id, *count),
+            Edit::RemoveAllCards => {
+                save.cards.clear();
+                Ok(())
+            }
+            Edit::RemoveCardAt { index } => {
+                card_at(save, *index)?;
+                save.cards.remove(*index);
+                Ok(())
+            }
+            Edit::UpgradeCard { index } => upgrade_card(cache, save, *index),
+            Edit::UpgradeAllCards => {
+                upgrade_all_cards(cache, save);
+                Ok(())
+            }
+            Edit::DowngradeCard { index } => downgrade_card(save, *index),
+            Edit::SetCardMisc { index, misc } => set_card_misc(cache, save, *index, *misc),
+            Edit::AddRandomCards { count, color } => {
+                add_random_cards(cache, save, rng, *count, |x| match color {
+                    Some(color) => x.color == *color,
+                    None => true,
+                })
+            }
+            Edit::AddRelic { id } => add_relic(cache, save, id),
+            Edit::RemoveRelic { id } => remove_relic(save, id),
+            Edit::AddRandomRelics { count, tier } => {
+                add_random_relics(cache, save, rng, *count, *tier)
+            }
+            Edit::SetPotion { slot, id } => set_potion(cache, save, *slot, id),
+            Edit::FillPotions { id } => fill_potions(cache, save, rng, id.as_deref()),
+            Edit::ClearPotions { slot } => clear_potions(save, *slot),
+            Edit::SetPotionSlots { count } => set_potion_slots(save, *count),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::Potion;
+    use crate::cache_enums::{CardRarity, CardType};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use serde_json::json;
+
+    fn card(id: &str, max_upgrades: Option<u32>, misc: Option<u32>) -> Card {
+        Card {
+            rarity: CardRarity::COMMON,
+            color: CardColor::RED,
+            type_: CardType::ATTACK,
+            misc: misc.unwrap_or(0),
+            uses_misc: misc.is_some(),
+            max_upgrades,
+            id: id.to_owned(),
+            name: None,
+        }
+    }
+
+    fn relic(id: &str, tier: RelicTier) -> Relic {
+        Relic {
+            tier,
+            id: id.to_owned(),
+            name: None,
+        }
+    }
+
+    fn potion(id: &str, rarity: PotionRarity, class: Option<PlayerClass>) -> Potion {
+        Potion {
+            rarity,
+            class,
+            id: id.to_owned(),
+            name: None,
+        }
+    }
+
+    fn test_cache() -> STSCache {
+        STSCache {
+            cards: vec![
+                card("Strike_R", Some(1), None),
+                card("Searing Blow", None, None),
+                card("Genetic Algorithm", Some(1), Some(1)),
+            ],
+            relics: vec![
+                relic("Burning Blood", RelicTier::STARTER),
+                relic("Anchor", RelicTier::COMMON),
+                relic("Pen Nib", RelicTier::COMMON),
+                relic("Vajra", RelicTier::COMMON),
+            ],
+            potions: vec![
+                potion("Fire Potion", PotionRarity::COMMON, None),
+                potion(
+                    "Poison Potion",
+                    PotionRarity::COMMON,
+                    Some(PlayerClass::THE_SILENT),
+                ),
+                potion("Potion Slot", PotionRarity::PLACEHOLDER, None),
+            ],
+        }
+    }
+
+    fn test_save() -> SaveFile {
+        SaveFile::from_json(json!({
+            "loadout": "IRONCLAD",
+            "current_health": 60,
+            "max_health": 80,
+            "gold": 99,
+            "gold_gained": 0,
+            "cards": [
+                {"id": "Strike_R", "upgrades": 0, "misc": 0},
+                {"id": "Searing Blow", "upgrades": 3, "misc": 0},
+                {"id": "Genetic Algorithm", "upgrades": 0, "misc": 7},
+                {"id": "AscendersBane", "upgrades": 0, "misc": 0}
+            ],
+            "relics": ["Burning Blood", "Pen Nib"],
+            "relic_counters": [-1, 4],
+            "potions": ["Potion Slot", "Fire Potion"],
+            "potion_slots": 3,
+            "metric_current_hp_per_floor": [80, 60],
+            "metric_max_hp_per_floor": [80, 80]
+        }))
+        .unwrap()
+    }
+
+    fn apply(save: &mut SaveFile, edit: Edit) -> Result<(), SaveError> {
+        edit.apply(save, &test_cache(), &mut StdRng::seed_from_u64(7))
+    }
+
+    #[test]
+    fn add_gold_fails_instead_of_overflowing() {
+        let mut save = test_save();
+        let edit = Edit::AddGold { amount: i32::MAX };
+        assert!(matches!(
+            apply(&mut save, edit),
+            Err(SaveError::InvalidValue { .. })
+        ));
+        apply(&mut save, Edit::AddGold { amount: -500 }).unwrap();
+        assert_eq!(save.gold, 0);
+    }
+
+    #[test]
+    fn set_health_keeps_current_within_max() {
+        let mut save = test_save();
+        let edit = Edit::SetHealth {
+            current: Some(90),
+            max: None,
+            record: false,
+        };
+        assert!(matches!(
+            apply(&mut save, edit),
+            Err(SaveError::InvalidValue { .. })
+        ));
+        assert_eq!((save.current_health, save.max_health), (60, 80));
+
+        let edit = Edit::SetHealth {
+            current: None,
+            max: Some(50),
+            record: false,
+        };
+        apply(&mut save, edit).unwrap();
+        assert_eq!((save.current_health, save.max_health), (50, 50));
+    }
+
+    #[test]
+    fn validate_rejects_health_below_one() {
+        let edit = Edit::SetHealth {
+            current: Some(0),
+            max: Some(80),
+            record: false,
+        };
+        assert!(edit.validate(&test_cache()).is_err());
+    }
+
+    #[test]
+    fn add_health_clamps_to_one_and_max() {
+        let mut save = test_save();
+        let edit = Edit::AddHealth {
+            current: 100,
+            max: 0,
+            record: false,
+        };
+        apply(&mut save, edit).unwrap();
+        assert_eq!((save.current_health, save.max_health), (80, 80));
+
+        let edit = Edit::AddHealth {
+            current: -500,
+            max: -500,
+            record: false,
+        };
+        apply(&mut save, edit).unwrap();
+        assert_eq!((save.current_health, save.max_health), (1, 1));
+    }
+
+    #[test]
+    fn add_health_fails_instead_of_overflowing() {
+        let mut save = test_save();
+        let edit = Edit::AddHealth {
+            current: 0,
+            max: i32::MAX,
+            record: false,
+        };
+        assert!(matches!(
+            apply(&mut save, edit),
+            Err(SaveError::InvalidValue { .. })
+        ));
+        let edit = Edit::AddHealth {
+            current: i32::MAX,
+            max: 0,
+            record: false,
+        };
+        assert!(matches!(
+            apply(&mut save, edit),
+            Err(SaveError::InvalidValue { .. })
+        ));
+        assert_eq!((save.current_health, save.max_health), (60, 80));
+    }
+
+    #[test]
+    fn heal_records_the_current_floor() {
+        let mut save = test_save();
+        apply(&mut save, Edit::Heal { record: true }).unwrap();
+        assert_eq!(save.current_health, 80);
+        assert_eq!(save.metric_current_hp_per_floor, Some(vec![80, 80]));
+        assert_eq!(save.metric_max_hp_per_floor, Some(vec![80, 80]));
+    }
+
+    #[test]
+    fn recording_needs_the_hp_metrics() {
+        let mut save = test_save();
+        save.metric_max_hp_per_floor = None;
+        assert!(matches!(
+            apply(&mut save, Edit::Heal { record: true }),
+            Err(SaveError::MissingField { .. })
+        ));
+    }
+
+    fn assert_aligned(save: &SaveFile) {
+        assert_eq!(
+            save.relic_counters.as_ref().map(Vec::len),
+            Some(save.relics.len())
+        );
+    }
+
+    #[test]
+    fn added_relics_get_a_counter() {
+        let mut save = test_save();
+        save.relic_counters = Some(vec![-1]);
+        let edit = Edit::AddRelic {
+            id: "Anchor".to_owned(),
+        };
+        apply(&mut save, edit).unwrap();
+        assert_eq!(save.relics, ["Burning Blood", "Pen Nib", "Anchor"]);
+        assert_eq!(save.relic_counters, Some(vec![-1, -1, -1]));
+
+        let edit = Edit::AddRelic {
+            id: "Pen Nib".to_owned(),
+        };
+        assert!(matches!(
+            apply(&mut save, edit),
+            Err(SaveError::RelicAlreadyOwned { .. })
+        ));
+        assert_eq!(save.relics, ["Burning Blood", "Pen Nib", "Anchor"]);
+    }
+
+    #[test]
+    fn removed_relics_take_their_counter_along() {
+        let mut save = test_save();
+        let edit = Edit::RemoveRelic {
+            id: "Burning Blood".to_owned(),
+        };
+        apply(&mut save, edit).unwrap();
+        assert_eq!(save.relics, ["Pen Nib"]);
+        assert_eq!(save.relic_counters, Some(vec![4]));
+
+        let edit = Edit::RemoveRelic {
+            id: "Anchor".to_owned(),
+        };
+        assert!(matches!(
+            apply(&mut save, edit),
+            Err(SaveError::RelicNotOwned { .. })
+        ));
+    }
+
+    #[test]
+    fn random_relics_are_new_and_distinct() {
+        let mut save = test_save();
+        let edit = Edit::AddRandomRelics {
+            count: 2,
+            tier: Some(RelicTier::COMMON),
+        };
+        apply(&mut save, edit).unwrap();
+        let mut relics = save.relics.clone();
+        relics.sort();
+        assert_eq!(relics, ["Anchor", "Burning Blood", "Pen Nib", "Vajra"]);
+        assert_aligned(&save);
+    }
+
+    #[test]
+    fn random_relics_fail_when_too_few_are_left() {
+        let mut save = test_save();
+        let edit = Edit::AddRandomRelics {
+            count: 3,
+            tier: Some(RelicTier::COMMON),
+        };
+        assert_eq!(
+            apply(&mut save, edit),
+            Err(SaveError::NotEnoughRelics {
+                requested: 3,
+                available: 2
+            })
+        );
+        assert_eq!(save.relics.len(), 2);
+
+        let edit = Edit::AddRandomRelics {
+            count: 1,
+            tier: Some(RelicTier::BOSS),
+        };
+        assert_eq!(apply(&mut save, edit), Err(SaveError::NoMatchingRelics));
+    }
+
+    #[test]
+    fn potions_fill_up_to_the_slot_count() {
+        let mut save = test_save();
+        apply(&mut save, Edit::FillPotions { id: None }).unwrap();
+        // Only potions every character can find, never the Silent's or the placeholder.
+        assert_eq!(save.potions, Some(vec!["Fire Potion".to_owned(); 3]));
+    }
+
+    #[test]
+    fn potion_slots_are_checked() {
+        let mut save = test_save();
+        let edit = Edit::SetPotion {
+            slot: 3,
+            id: "Fire Potion".to_owned(),
+        };
+        assert_eq!(
+            apply(&mut save, edit),
+            Err(SaveError::NoPotionSlot { slot: 3 })
+        );
+        let edit = Edit::SetPotion {
+            slot: 0,
+            id: "Elixir".to_owned(),
+        };
+        assert!(matches!(
+            apply(&mut save, edit),
+            Err(SaveError::UnknownPotionId { .. })
+        ));
+    }
+
+    #[test]
+    fn dropped_potion_slots_have_to_be_empty() {
+        let mut save = test_save();
+        let edit = Edit::SetPotionSlots { count: 1 };
+        assert!(matches!(
+            apply(&mut save, edit),
+            Err(SaveError::InvalidValue { .. })
+        ));
+
+        apply(&mut save, Edit::ClearPotions { slot: Some(1) }).unwrap();
+        apply(&mut save, Edit::SetPotionSlots { count: 1 }).unwrap();
+        assert_eq!(save.potion_slots, Some(1));
+        assert_eq!(save.potions, Some(vec![EMPTY_POTION_SLOT.to_owned()]));
+    }
+
+    fn upgrades(save: &SaveFile) -> Vec<i32> {
+        save.cards.iter().map(|x| x.upgrades).collect()
+    }
+
+    #[test]
+    fn cards_upgrade_up_to_their_limit() {
+        let mut save = test_save();
+        apply(&mut save, Edit::UpgradeCard { index: 0 }).unwrap();
+        assert_eq!(
+            apply(&mut save, Edit::UpgradeCard { index: 0 }),
+            Err(SaveError::CannotUpgrade {
+                id: "Strike_R".to_owned()
+            })
+        );
+        // Searing Blow has no limit.
+        apply(&mut save, Edit::UpgradeCard { index: 1 }).unwrap();
+        assert_eq!(upgrades(&save), [1, 4, 0, 0]);
+        assert_eq!(
+            apply(&mut save, Edit::UpgradeCard { index: 9 }),
+            Err(SaveError::NoCardAtIndex { index: 9 })
+        );
+    }
+
+    #[test]
+    fn upgrade_all_skips_maxed_and_unknown_cards() {
+        let mut save = test_save();
+        apply(&mut save, Edit::UpgradeCard { index: 0 }).unwrap();
+        apply(&mut save, Edit::UpgradeAllCards).unwrap();
+        assert_eq!(upgrades(&save), [1, 4, 1, 0]);
+    }
+
+    #[test]
+    fn downgrades_stop_at_zero() {
+        let mut save = test_save();
+        apply(&mut save, Edit::DowngradeCard { index: 1 }).unwrap();
+        assert_eq!(upgrades(&save), [0, 2, 0, 0]);
+        assert_eq!(
+            apply(&mut save, Edit::DowngradeCard { index: 0 }),
+            Err(SaveError::NotUpgraded {
+                id: "Strike_R".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn added_cards_respect_the_upgrade_limit() {
+        let cache = test_cache();
+        let edit = |id: &str, upgrades| Edit::AddCard {
+            id: id.to_owned(),
+            upgrades,
+            count: 1,
+        };
+        assert!(edit("Strike_R", 1).validate(&cache).is_ok());
+        assert!(edit("Strike_R", 2).validate(&cache).is_err());
+        assert!(edit("Strike_R", -1).validate(&cache).is_err());
+        assert!(edit("Searing Blow", 12).validate(&cache).is_ok());
+    }
+
+    #[test]
+    fn misc_is_only_set_on_cards_that_use_it() {
+        let mut save = test_save();
+        apply(&mut save, Edit::SetCardMisc { index: 2, misc: 30 }).unwrap();
+        assert_eq!(save.cards[2].misc, 30);
+        assert!(matches!(
+            apply(&mut save, Edit::SetCardMisc { index: 0, misc: 30 }),
+            Err(SaveError::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            apply(&mut save, Edit::SetCardMisc { index: 3, misc: 30 }),
+            Err(SaveError::UnknownCardId { .. })
+        ));
+        assert_eq!(save.cards[0].misc, 0);
+    }
+}
@@ -0,0 +1,118 @@
+/// Problems with the contents of a save, as opposed to failures to read or write it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaveError {
+    /// A field the action needs is not present; `path` is the JSON path to it.
+    MissingField { path: String },
+    /// A field holds a value of the wrong type or range.
+    WrongType { path: String, message: String },
+    /// A value the game would not accept, such as current HP above max HP.
+    InvalidValue { field: String, message: String },
+    /// A card id that does not exist in `STSCache`.
+    UnknownCardId { id: String },
+    /// A card id that is not in the deck.
+    CardNotInDeck { id: String },
+    /// A deck position past the end of the deck.
+    NoCardAtIndex { index: usize },
+    /// A card that already has as many upgrades as the game allows.
+    CannotUpgrade { id: String },
+    /// A card without upgrades to take away.
+    NotUpgraded { id: String },
+    /// A relic id that does not exist in `STSCache`.
+    UnknownRelicId { id: String },
+    /// A potion id that does not exist in `STSCache`.
+    UnknownPotionId { id: String },
+    /// A potion slot past the last one the save has.
+    NoPotionSlot { slot: usize },
+    /// A relic id that the save does not have.
+    RelicNotOwned { id: String },
+    /// A relic id that the save already has.
+    RelicAlreadyOwned { id: String },
+    /// No card in `STSCache` passes the filter of a random pick.
+    NoMatchingCards,
+    /// No relic in `STSCache` that the save lacks passes the filter of a random pick.
+    NoMatchingRelics,
+    /// Fewer relics than a random pick asks for pass its filter.
+    NotEnoughRelics { requested: usize, available: usize },
+    /// No potion in `STSCache` can be found by the save's character.
+    NoMatchingPotions,
+}
+
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveError::MissingField { path } => write!(f, "Missing field '{}'", path),
+            SaveError::WrongType { path, message } => {
+                write!(f, "Wrong type of field '{}': {}", path, message)
+            }
+            SaveError::InvalidValue { field, message } => {
+                write!(f, "Invalid value for '{}': {}", field, message)
+            }
+            SaveError::UnknownCardId { id } => write!(f, "Unknown card id '{}'", id),
+            SaveError::CardNotInDeck { id } => write!(f, "Card '{}' is not in the deck", id),
+            SaveError::NoCardAtIndex { index } => {
+                write!(f, "There is no card #{} in the deck", index)
+            }
+            SaveError::CannotUpgrade { id } => {
+                write!(f, "Card '{}' cannot be upgraded further", id)
+            }
+            SaveError::NotUpgraded { id } => write!(f, "Card '{}' is not upgraded", id),
+            SaveError::UnknownRelicId { id } => write!(f, "Unknown relic id '{}'", id),
+            SaveError::UnknownPotionId { id } => write!(f, "Unknown potion id '{}'", id),
+            SaveError::NoPotionSlot { slot } => write!(f, "There is no potion slot #{}", slot),
+            SaveError::RelicNotOwned { id } => write!(f, "Relic '{}' is not owned", id),
+            SaveError::RelicAlreadyOwned { id } => write!(f, "Relic '{}' is already owned", id),
+            SaveError::NoMatchingCards => write!(f, "No card in the cache matches the filter"),
+            SaveError::NoMatchingRelics => {
+                write!(
+                    f,
+                    "No relic in the cache that is not owned yet matches the filter"
+                )
+            }
+            SaveError::NotEnoughRelics {
+                requested,
+                available,
+            } => write!(
+                f,
+                "Asked for {} random relics, but only {} not owned yet match the filter",
+                requested, available
+            ),
+            SaveError::NoMatchingPotions => {
+                write!(f, "No potion in the cache can be found by this character")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl SaveError {
+    pub fn missing(path: &str) -> Self {
+        SaveError::MissingField {
+            path: path.to_owned(),
+        }
+    }
+
+    pub fn invalid(field: &str, message: &str) -> Self {
+        SaveError::InvalidValue {
+            field: field.to_owned(),
+            message: message.to_owned(),
+        }
+    }
+
+    /// Converts a deserialization error into a `SaveError` pointing at the offending field.
+    pub fn from_path_error(error: serde_path_to_error::Error<serde_json::Error>) -> Self {
+        let path = error.path().to_string();
+        let message = error.into_inner().to_string();
+        // serde reports missing fields as "missing field `name`" on the parent object.
+        if let Some(rest) = message.strip_prefix("missing field `") {
+            let field = rest.split('`').next().unwrap_or_default();
+            let path = if path == "." {
+                field.to_owned()
+            } else {
+                format!("{}.{}", path, field)
+            };
+            return SaveError::MissingField { path };
+        }
+        SaveError::WrongType { path, message }
+    }
+}
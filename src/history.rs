@@ -0,0 +1,139 @@
+use crate::save_file::SaveFile;
+
+struct HistoryEntry {
+    label: String,
+    save: SaveFile,
+}
+
+/// Snapshots of the save after every edit of a session, for undo and redo.
+pub struct History {
+    entries: Vec<HistoryEntry>,
+    current: usize,
+}
+
+impl History {
+    pub fn new(initial: SaveFile) -> Self {
+        History {
+            entries: vec![HistoryEntry {
+                label: "Loaded save".to_owned(),
+                save: initial,
+            }],
+            current: 0,
+        }
+    }
+
+    pub fn current(&self) -> &SaveFile {
+        &self.entries[self.current].save
+    }
+
+    /// Adds the state after an edit, dropping any states that were undone before it.
+    pub fn record(&mut self, label: String, save: SaveFile) {
+        self.entries.truncate(self.current + 1);
+        self.entries.push(HistoryEntry { label, save });
+        self.current += 1;
+    }
+
+    pub fn undo(&mut self) -> Option<&SaveFile> {
+        if self.current == 0 {
+            return None;
+        }
+        self.current -= 1;
+        Some(self.current())
+    }
+
+    pub fn redo(&mut self) -> Option<&SaveFile> {
+        if self.current + 1 >= self.entries.len() {
+            return None;
+        }
+        self.current += 1;
+        Some(self.current())
+    }
+
+    /// Moves to the state at `index`, keeping later states available for redo.
+    pub fn go_to(&mut self, index: usize) -> Option<&SaveFile> {
+        if !self.has_state(index) {
+            return None;
+        }
+        self.current = index;
+        Some(self.current())
+    }
+
+    /// Whether there is a state numbered `index` to go to.
+    pub fn has_state(&self, index: usize) -> bool {
+        index < self.entries.len()
+    }
+
+    pub fn print(&self) {
+        for (index, entry) in self.entries.iter().enumerate() {
+            let marker = if index == self.current { "->" } else { "  " };
+            println!("{} {}: {}", marker, index, entry.label);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn save_with_gold(gold: i32) -> SaveFile {
+        SaveFile::from_json(json!({
+            "current_health": 60,
+            "max_health": 80,
+            "gold": gold,
+            "cards": [],
+            "relics": [],
+        }))
+        .unwrap()
+    }
+
+    fn history_with_gold(golds: &[i32]) -> History {
+        let mut history = History::new(save_with_gold(golds[0]));
+        for gold in &golds[1..] {
+            history.record(format!("Gold {}", gold), save_with_gold(*gold));
+        }
+        history
+    }
+
+    #[test]
+    fn records_become_the_current_state() {
+        let history = history_with_gold(&[0, 10, 20]);
+        assert_eq!(history.current().gold, 20);
+        assert!(history.has_state(2));
+        assert!(!history.has_state(3));
+    }
+
+    #[test]
+    fn undo_and_redo_walk_the_states() {
+        let mut history = history_with_gold(&[0, 10, 20]);
+        assert_eq!(history.undo().map(|x| x.gold), Some(10));
+        assert_eq!(history.undo().map(|x| x.gold), Some(0));
+        assert!(history.undo().is_none());
+        assert_eq!(history.current().gold, 0);
+        assert_eq!(history.redo().map(|x| x.gold), Some(10));
+        assert_eq!(history.redo().map(|x| x.gold), Some(20));
+        assert!(history.redo().is_none());
+        assert_eq!(history.current().gold, 20);
+    }
+
+    #[test]
+    fn go_to_keeps_later_states() {
+        let mut history = history_with_gold(&[0, 10, 20]);
+        assert_eq!(history.go_to(0).map(|x| x.gold), Some(0));
+        assert!(history.go_to(3).is_none());
+        assert_eq!(history.current().gold, 0);
+        assert_eq!(history.go_to(2).map(|x| x.gold), Some(20));
+    }
+
+    #[test]
+    fn edits_after_undo_drop_the_redo_states() {
+        let mut history = history_with_gold(&[0, 10, 20]);
+        history.undo();
+        history.undo();
+        history.record("Gold 5".to_owned(), save_with_gold(5));
+        assert_eq!(history.current().gold, 5);
+        assert!(history.redo().is_none());
+        assert!(!history.has_state(2));
+        assert_eq!(history.undo().map(|x| x.gold), Some(0));
+    }
+}
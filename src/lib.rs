@@ -0,0 +1,16 @@
+pub mod backup;
+pub mod batch;
+pub mod cache;
+pub mod cache_enums;
+pub mod codec;
+pub mod diff;
+pub mod edits;
+pub mod error;
+pub mod history;
+pub mod matching;
+pub mod patch;
+pub mod save_editor;
+pub mod save_file;
+pub mod script;
+pub mod status;
+pub mod tui;
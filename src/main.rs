@@ -1,22 +1,676 @@
-use anyhow::{Context, Result as AnyResult};
+use anyhow::{anyhow, Context, Result as AnyResult};
+use clap::{ArgGroup, Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::engine::{ArgValueCandidates, CompletionCandidate};
+use clap_complete::env::{self, EnvCompleter};
+use clap_complete::CompleteEnv;
 
-mod cache;
-mod cache_enums;
-mod save_editor;
+use std::path::{Path, PathBuf};
+
+use stsse::cache::STSCache;
+use stsse::cache_enums::RelicTier;
+use stsse::codec::Codec;
+use stsse::diff::SaveDiff;
+use stsse::edits::Edit;
+use stsse::error::SaveError;
+use stsse::matching::{self, Candidate};
+use stsse::patch;
+use stsse::save_editor::EditorOptions;
+use stsse::save_file::SaveFile;
+use stsse::script::EditScript;
+use stsse::status::SaveStatus;
+use stsse::{backup, batch, save_editor, tui};
+
+#[derive(Parser)]
+#[command(name = "stsse", about = "Slay the Spire save editor")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Save file to edit; looked up in the current folder when omitted.
+    save: Option<PathBuf>,
+    /// XOR key the save is obfuscated with ('hex:' prefix for raw bytes, e.g. hex:6b6579).
+    #[arg(long, global = true)]
+    key: Option<String>,
+    /// Number of timestamped backups to keep next to an edited save (0 disables them).
+    #[arg(long, global = true, default_value_t = backup::DEFAULT_BACKUP_COUNT)]
+    backups: usize,
+    /// Show the changes an edit would make without writing the save.
+    #[arg(long, global = true)]
+    dry_run: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ShowFormat {
+    Text,
+    Json,
+    Tsv,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    fn completer(self) -> &'static dyn EnvCompleter {
+        match self {
+            Shell::Bash => &env::Bash,
+            Shell::Zsh => &env::Zsh,
+            Shell::Fish => &env::Fish,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Check that re-encoding an unedited save reproduces it byte for byte.
+    VerifyRoundtrip { save: Option<PathBuf> },
+    /// Work out the XOR key of an obfuscated save.
+    RecoverKey { save: Option<PathBuf> },
+    /// Decrypt every .autosave in a folder into a .json sidecar, backing the save up first.
+    DecryptDir { folder: Option<PathBuf> },
+    /// Encrypt every .json sidecar in a folder back into its save and delete the sidecar.
+    EncryptDir { folder: Option<PathBuf> },
+    /// Show what changed between two saves.
+    Diff {
+        before: PathBuf,
+        after: PathBuf,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Print the stats, deck, relics and potions of a save.
+    Show {
+        save: Option<PathBuf>,
+        #[arg(long, value_enum, default_value_t = ShowFormat::Text)]
+        format: ShowFormat,
+    },
+    /// Change the gold of a save.
+    Gold {
+        #[command(subcommand)]
+        action: GoldAction,
+        #[command(flatten)]
+        target: SaveTarget,
+    },
+    /// Change the current or max HP of a save.
+    Hp {
+        #[command(subcommand)]
+        action: HpAction,
+        #[command(flatten)]
+        target: SaveTarget,
+    },
+    /// Change the deck of a save.
+    Card {
+        #[command(subcommand)]
+        action: CardAction,
+        #[command(flatten)]
+        target: SaveTarget,
+    },
+    /// Change the relics of a save.
+    Relic {
+        #[command(subcommand)]
+        action: RelicAction,
+        #[command(flatten)]
+        target: SaveTarget,
+    },
+    /// Change the potions of a save.
+    Potion {
+        #[command(subcommand)]
+        action: PotionAction,
+        #[command(flatten)]
+        target: SaveTarget,
+    },
+    /// Apply or produce RFC 6902 JSON Patches for saves.
+    Patch {
+        #[command(subcommand)]
+        action: PatchAction,
+    },
+    /// Browse and edit a save in a full-screen terminal UI.
+    Tui { save: Option<PathBuf> },
+    /// Print the shell code that sets up tab completion, e.g. `source <(stsse completions bash)`.
+    Completions { shell: Shell },
+    /// Apply an edit script (.toml, .yaml or .json) to a save in one go.
+    Apply {
+        script: PathBuf,
+        #[command(flatten)]
+        target: SaveTarget,
+    },
+}
+
+#[derive(Args)]
+struct SaveTarget {
+    /// Save file to edit; looked up in the current folder when omitted.
+    #[arg(long, short, global = true)]
+    save: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum PatchAction {
+    /// Apply a JSON Patch to the decoded save.
+    Apply {
+        patch: PathBuf,
+        #[command(flatten)]
+        target: SaveTarget,
+    },
+    /// Produce the JSON Patch that turns one save into another.
+    Diff {
+        before: PathBuf,
+        after: PathBuf,
+        /// File to write the patch to instead of printing it.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum GoldAction {
+    /// Add gold (negative amounts take it away).
+    Add {
+        #[arg(allow_hyphen_values = true)]
+        amount: i32,
+    },
+}
+
+#[derive(Subcommand)]
+enum HpAction {
+    /// Set current and/or max HP; current HP has to stay between 1 and max HP.
+    #[command(group(ArgGroup::new("health").required(true).multiple(true).args(["current", "max"])))]
+    Set {
+        #[arg(long)]
+        current: Option<i32>,
+        #[arg(long)]
+        max: Option<i32>,
+        /// Also change this floor's HP in the run history.
+        #[arg(long)]
+        record: bool,
+    },
+    /// Add current and/or max HP (negative amounts take it away).
+    #[command(group(ArgGroup::new("health").required(true).multiple(true).args(["current", "max"])))]
+    Add {
+        #[arg(long, allow_hyphen_values = true)]
+        current: Option<i32>,
+        #[arg(long, allow_hyphen_values = true)]
+        max: Option<i32>,
+        /// Also change this floor's HP in the run history.
+        #[arg(long)]
+        record: bool,
+    },
+    /// Heal to full HP.
+    Heal {
+        /// Also change this floor's HP in the run history.
+        #[arg(long)]
+        record: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum CardAction {
+    /// Add a card to the deck.
+    Add {
+        #[arg(add = ArgValueCandidates::new(card_id_candidates))]
+        id: String,
+        #[arg(long, default_value_t = 0)]
+        upgrades: i32,
+        #[arg(long, default_value_t = 1)]
+        count: usize,
+    },
+    /// Remove a card from the deck, every copy of it unless --count is given.
+    Remove {
+        #[arg(add = ArgValueCandidates::new(card_id_candidates))]
+        id: String,
+        #[arg(long)]
+        count: Option<usize>,
+    },
+    /// Upgrade the card at a deck position (counted from 0), or every card with --all.
+    Upgrade {
+        #[arg(required_unless_present = "all")]
+        index: Option<usize>,
+        #[arg(long, conflicts_with = "index")]
+        all: bool,
+    },
+    /// Take one upgrade away from the card at a deck position (counted from 0).
+    Downgrade { index: usize },
+    /// Show the misc value of the card at a deck position (counted from 0), or set it.
+    Misc { index: usize, value: Option<i32> },
+}
+
+#[derive(Subcommand)]
+enum RelicAction {
+    /// Add a relic, by id or by a name that matches only one relic.
+    Add {
+        #[arg(add = ArgValueCandidates::new(relic_id_candidates))]
+        id: String,
+    },
+    /// Remove a relic and its counter.
+    Remove {
+        #[arg(add = ArgValueCandidates::new(owned_relic_candidates))]
+        id: String,
+    },
+    /// Add random relics the save does not have yet.
+    Random {
+        /// Only pick relics of this tier, e.g. COMMON, RARE or BOSS.
+        #[arg(long, value_parser = parse_relic_tier)]
+        tier: Option<RelicTier>,
+        #[arg(long, default_value_t = 1)]
+        count: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum PotionAction {
+    /// Put a potion into a slot (counted from 0), replacing what is there.
+    Set {
+        slot: usize,
+        #[arg(add = ArgValueCandidates::new(potion_id_candidates))]
+        id: String,
+    },
+    /// Fill every empty slot with a potion, or with random potions the character can find.
+    Fill {
+        #[arg(add = ArgValueCandidates::new(potion_id_candidates))]
+        id: Option<String>,
+    },
+    /// Empty a potion slot, or every slot when --slot is not given.
+    Clear {
+        #[arg(long)]
+        slot: Option<usize>,
+    },
+    /// Change how many potion slots there are.
+    Slots { count: usize },
+}
+
+impl GoldAction {
+    fn into_edit(self) -> Edit {
+        match self {
+            GoldAction::Add { amount } => Edit::AddGold { amount },
+        }
+    }
+}
+
+impl HpAction {
+    fn into_edit(self) -> Edit {
+        match self {
+            HpAction::Set {
+                current,
+                max,
+                record,
+            } => Edit::SetHealth {
+                current,
+                max,
+                record,
+            },
+            HpAction::Add {
+                current,
+                max,
+                record,
+            } => Edit::AddHealth {
+                current: current.unwrap_or(0),
+                max: max.unwrap_or(0),
+                record,
+            },
+            HpAction::Heal { record } => Edit::Heal { record },
+        }
+    }
+}
+
+impl CardAction {
+    /// The edit the action makes, `None` for `misc` without a value, which only shows it.
+    /// Ids of cards to add are looked up in the cache, ids of cards to remove in the deck.
+    fn into_edit(self, cache: &STSCache, save: &SaveFile) -> AnyResult<Option<Edit>> {
+        Ok(Some(match self {
+            CardAction::Add {
+                id,
+                upgrades,
+                count,
+            } => Edit::AddCard {
+                id: resolve_id(
+                    "card",
+                    &id,
+                    &cache.card_candidates(cache.cards.iter().map(|x| &x.id)),
+                )?,
+                upgrades,
+                count,
+            },
+            CardAction::Remove { id, count } => Edit::RemoveCard {
+                id: resolve_id("card", &id, &cache.card_candidates(&save.card_ids()))?,
+                count,
+            },
+            CardAction::Upgrade {
+                index: Some(index), ..
+            } => Edit::UpgradeCard { index },
+            CardAction::Upgrade { index: None, .. } => Edit::UpgradeAllCards,
+            CardAction::Downgrade { index } => Edit::DowngradeCard { index },
+            CardAction::Misc {
+                index,
+                value: Some(misc),
+            } => Edit::SetCardMisc { index, misc },
+            CardAction::Misc { value: None, .. } => return Ok(None),
+        }))
+    }
+}
+
+impl RelicAction {
+    /// Ids of relics to add are looked up in the cache, ids of relics to remove among the
+    /// relics of `save`, which can include relics the cache does not know.
+    fn into_edit(self, cache: &STSCache, save: &SaveFile) -> AnyResult<Edit> {
+        Ok(match self {
+            RelicAction::Add { id } => Edit::AddRelic {
+                id: resolve_id(
+                    "relic",
+                    &id,
+                    &cache.relic_candidates(cache.relics.iter().map(|x| &x.id)),
+                )?,
+            },
+            RelicAction::Remove { id } => Edit::RemoveRelic {
+                id: resolve_id("relic", &id, &cache.relic_candidates(&save.relics))?,
+            },
+            RelicAction::Random { tier, count } => Edit::AddRandomRelics { count, tier },
+        })
+    }
+}
+
+impl PotionAction {
+    fn into_edit(self, cache: &STSCache) -> AnyResult<Edit> {
+        let potions = cache.potion_candidates(cache.potions.iter().map(|x| &x.id));
+        Ok(match self {
+            PotionAction::Set { slot, id } => Edit::SetPotion {
+                slot,
+                id: resolve_id("potion", &id, &potions)?,
+            },
+            PotionAction::Fill { id } => Edit::FillPotions {
+                id: id.map(|x| resolve_id("potion", &x, &potions)).transpose()?,
+            },
+            PotionAction::Clear { slot } => Edit::ClearPotions { slot },
+            PotionAction::Slots { count } => Edit::SetPotionSlots { count },
+        })
+    }
+}
+
+fn parse_relic_tier(tier: &str) -> Result<RelicTier, String> {
+    RelicTier::from_str(&tier.to_uppercase())
+        .ok_or_else(|| format!("unknown relic tier '{}'", tier))
+}
+
+/// Picks the id that `needle` matches best, as long as no other id matches as well.
+fn resolve_id(what: &str, needle: &str, candidates: &[Candidate]) -> AnyResult<String> {
+    match matching::find_matches(needle, candidates).as_slice() {
+        [] => Err(anyhow!("No {} matches '{}'", what, needle)),
+        [only] => Ok(only.id.clone()),
+        several => {
+            let names: Vec<String> = several.iter().map(|x| x.to_string()).collect();
+            Err(anyhow!(
+                "'{}' matches several {}s: {}",
+                needle,
+                what,
+                names.join(", ")
+            ))
+        }
+    }
+}
+
+fn load_cache(cwd: &Path) -> AnyResult<STSCache> {
+    STSCache::load_or_create_from_file_in_folder(cwd)
+        .with_context(|| format!("Failed to load STSCache from '{:?}'", cwd))
+}
+
+/// Loads the cache of the current folder while completing, where errors can only be ignored.
+/// Completing never builds the cache, so this only finds one that is already there.
+fn completion_cache() -> Option<STSCache> {
+    let cwd = std::env::current_dir().ok()?;
+    STSCache::load_from_folder(&cwd).ok()
+}
+
+/// Turns `(id, name)` pairs into candidates, showing the display name next to an id when
+/// that says something else.
+fn to_candidates(
+    entries: impl IntoIterator<Item = (String, Option<String>)>,
+) -> Vec<CompletionCandidate> {
+    entries
+        .into_iter()
+        .map(|(id, name)| {
+            let help = name.filter(|x| *x != id).map(Into::into);
+            CompletionCandidate::new(id).help(help)
+        })
+        .collect()
+}
+
+/// Completes to the ids of one list of the cache.
+fn id_candidates<I>(entries: impl FnOnce(STSCache) -> I) -> Vec<CompletionCandidate>
+where
+    I: IntoIterator<Item = (String, Option<String>)>,
+{
+    match completion_cache() {
+        Some(cache) => to_candidates(entries(cache)),
+        None => Vec::new(),
+    }
+}
+
+fn card_id_candidates() -> Vec<CompletionCandidate> {
+    id_candidates(|cache| cache.cards.into_iter().map(|x| (x.id, x.name)))
+}
+
+fn relic_id_candidates() -> Vec<CompletionCandidate> {
+    id_candidates(|cache| cache.relics.into_iter().map(|x| (x.id, x.name)))
+}
+
+fn potion_id_candidates() -> Vec<CompletionCandidate> {
+    id_candidates(|cache| cache.potions.into_iter().map(|x| (x.id, x.name)))
+}
+
+/// Completes to the relics of the only save in the current folder, named from the cache
+/// when there is one.
+fn owned_relic_candidates() -> Vec<CompletionCandidate> {
+    let Ok(cwd) = std::env::current_dir() else {
+        return Vec::new();
+    };
+    let Ok(Some(savefile_path)) = save_editor::get_save_file_path(&cwd, None, false) else {
+        return Vec::new();
+    };
+    let Ok((save, _)) = save_editor::load_save(&savefile_path, &Codec::default()) else {
+        return Vec::new();
+    };
+    let cache = completion_cache();
+    to_candidates(save.relics.into_iter().map(|id| {
+        let name = cache
+            .as_ref()
+            .and_then(|cache| cache.relics.iter().find(|x| x.id == id))
+            .and_then(|x| x.name.clone());
+        (id, name)
+    }))
+}
+
+fn find_save_file(cwd: &Path, save: Option<PathBuf>, prompt: bool) -> AnyResult<Option<PathBuf>> {
+    let savefile_path = save_editor::get_save_file_path(cwd, save, prompt)?;
+    match &savefile_path {
+        Some(path) => eprintln!("Using save file {:?}", path),
+        None => eprintln!("Unable to find any save file."),
+    }
+    Ok(savefile_path)
+}
+
+fn require_save_file(cwd: &Path, save: Option<PathBuf>) -> AnyResult<PathBuf> {
+    find_save_file(cwd, save, false)?.ok_or_else(|| anyhow!("No save file to work on"))
+}
+
+fn edit_save_file(
+    cwd: &Path,
+    target: SaveTarget,
+    options: &EditorOptions,
+    edit: Edit,
+) -> AnyResult<()> {
+    let cache = load_cache(cwd)?;
+    let savefile_path = require_save_file(cwd, target.save)?;
+    save_editor::apply_edit(&savefile_path, &cache, options, &edit)
+}
+
+fn parse_key(key: &str) -> AnyResult<Vec<u8>> {
+    match key.strip_prefix("hex:") {
+        Some(hex) if hex.len() % 2 == 0 => (0..hex.len())
+            .step_by(2)
+            .map(|i| {
+                u8::from_str_radix(&hex[i..i + 2], 16)
+                    .with_context(|| format!("Invalid hex key '{}'", hex))
+            })
+            .collect(),
+        Some(hex) => Err(anyhow!("Hex key '{}' has an odd number of digits", hex)),
+        None => Ok(key.as_bytes().to_vec()),
+    }
+}
 
 fn main() -> AnyResult<()> {
+    // Answers the shell when it asks for completions through the COMPLETE variable.
+    CompleteEnv::with_factory(Cli::command).complete();
+    let cli = Cli::parse();
     let cwd = std::env::current_dir()?;
-    let cache = cache::STSCache::load_or_create_from_file_in_folder(&cwd)
-        .with_context(|| format!("Failed to load STSCache from '{:?}'", cwd))?;
-    println!("Cache loaded: {}", cache);
+    let codec = match &cli.key {
+        Some(key) => Codec::new(&parse_key(key)?)?,
+        None => Codec::default(),
+    };
+    let options = EditorOptions {
+        codec: codec.clone(),
+        backup_count: cli.backups,
+        dry_run: cli.dry_run,
+    };
 
-    let savefile_path = save_editor::get_save_file_path(&cwd);
-    if savefile_path.is_none() {
-        println!("Unable to find any save file.");
-        return Ok(());
-    }
-    println!("Using save file {:?}", savefile_path);
-    let savefile_path = savefile_path.unwrap();
+    match cli.command {
+        Some(Command::VerifyRoundtrip { save }) => {
+            let savefile_path = require_save_file(&cwd, save)?;
+            if !save_editor::verify_roundtrip(&savefile_path, &codec)? {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Some(Command::RecoverKey { save }) => {
+            let savefile_path = require_save_file(&cwd, save)?;
+            save_editor::recover_key(&savefile_path)?;
+            Ok(())
+        }
+        Some(Command::DecryptDir { folder }) => batch::decrypt_dir(&folder.unwrap_or(cwd), &codec),
+        Some(Command::EncryptDir { folder }) => batch::encrypt_dir(&folder.unwrap_or(cwd), &codec),
+        Some(Command::Diff {
+            before,
+            after,
+            format,
+        }) => {
+            let cache = load_cache(&cwd)?;
+            let (save_before, _) = save_editor::load_save(&before, &codec)?;
+            let (save_after, _) = save_editor::load_save(&after, &codec)?;
+            let diff = SaveDiff::between(&save_before, &save_after, &cache)?;
+            match format {
+                OutputFormat::Text => print!("{}", diff),
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&diff)?),
+            }
+            Ok(())
+        }
+        Some(Command::Show { save, format }) => {
+            let savefile_path = require_save_file(&cwd, save)?;
+            let (save, _) = save_editor::load_save(&savefile_path, &codec)?;
+            let status = SaveStatus::new(&save);
+            match format {
+                ShowFormat::Text => print!("{}", status),
+                ShowFormat::Json => println!("{}", serde_json::to_string_pretty(&status)?),
+                ShowFormat::Tsv => print!("{}", status.to_tsv()),
+            }
+            Ok(())
+        }
+        Some(Command::Gold { action, target }) => {
+            edit_save_file(&cwd, target, &options, action.into_edit())
+        }
+        Some(Command::Hp { action, target }) => {
+            edit_save_file(&cwd, target, &options, action.into_edit())
+        }
+        Some(Command::Card { action, target }) => {
+            let cache = load_cache(&cwd)?;
+            let savefile_path = require_save_file(&cwd, target.save)?;
+            let (save, _) = save_editor::load_save(&savefile_path, &codec)?;
+            if let CardAction::Misc { index, value: None } = action {
+                let card = save
+                    .cards
+                    .get(index)
+                    .ok_or(SaveError::NoCardAtIndex { index })?;
+                println!("{}", save_editor::describe_misc(card, &cache));
+            }
+            match action.into_edit(&cache, &save)? {
+                Some(edit) => save_editor::apply_edit(&savefile_path, &cache, &options, &edit),
+                None => Ok(()),
+            }
+        }
+        Some(Command::Relic { action, target }) => {
+            let cache = load_cache(&cwd)?;
+            let savefile_path = require_save_file(&cwd, target.save)?;
+            let (save, _) = save_editor::load_save(&savefile_path, &codec)?;
+            let edit = action.into_edit(&cache, &save)?;
+            save_editor::apply_edit(&savefile_path, &cache, &options, &edit)
+        }
+        Some(Command::Potion { action, target }) => {
+            let cache = load_cache(&cwd)?;
+            let edit = action.into_edit(&cache)?;
+            let savefile_path = require_save_file(&cwd, target.save)?;
+            save_editor::apply_edit(&savefile_path, &cache, &options, &edit)
+        }
+        Some(Command::Patch {
+            action: PatchAction::Apply { patch, target },
+        }) => {
+            let json_patch = patch::load_patch(&patch)
+                .with_context(|| format!("Failed to read JSON Patch '{:?}'", patch))?;
+            let cache = load_cache(&cwd)?;
+            let savefile_path = require_save_file(&cwd, target.save)?;
+            save_editor::apply_patch(&savefile_path, &cache, &options, &json_patch)
+        }
+        Some(Command::Patch {
+            action:
+                PatchAction::Diff {
+                    before,
+                    after,
+                    output,
+                },
+        }) => {
+            let (save_before, _) = save_editor::load_save(&before, &codec)?;
+            let (save_after, _) = save_editor::load_save(&after, &codec)?;
+            let json_patch = patch::make_patch(&save_before.to_json()?, &save_after.to_json()?);
+            let patch_text = serde_json::to_string_pretty(&json_patch)?;
+            match output {
+                Some(output) => std::fs::write(output, patch_text)?,
+                None => println!("{}", patch_text),
+            }
+            Ok(())
+        }
+        Some(Command::Apply { script, target }) => {
+            let script = EditScript::load(&script)
+                .with_context(|| format!("Failed to read edit script '{:?}'", script))?;
+            let cache = load_cache(&cwd)?;
+            let savefile_path = require_save_file(&cwd, target.save)?;
+            save_editor::apply_script(&savefile_path, &cache, &options, &script)
+        }
+        Some(Command::Completions { shell }) => {
+            let executable = std::env::current_exe()?;
+            shell.completer().write_registration(
+                "COMPLETE",
+                "stsse",
+                "stsse",
+                &executable.to_string_lossy(),
+                &mut std::io::stdout(),
+            )?;
+            Ok(())
+        }
+        Some(Command::Tui { save }) => {
+            let cache = load_cache(&cwd)?;
+            let savefile_path = require_save_file(&cwd, save)?;
+            tui::run(&savefile_path, &cache, &options)
+        }
+        None => {
+            let cache = load_cache(&cwd)?;
+            println!("Cache loaded: {}", cache);
 
-    save_editor::process_file(&savefile_path, &cache)
+            match find_save_file(&cwd, cli.save, true)? {
+                Some(savefile_path) => save_editor::process_file(&savefile_path, &cache, &options),
+                None => Ok(()),
+            }
+        }
+    }
 }
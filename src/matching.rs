@@ -0,0 +1,158 @@
+/// An id the user can pick, with the name the game displays for it when known.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub id: String,
+    pub name: Option<String>,
+}
+
+impl std::fmt::Display for Candidate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(name) if *name != self.id => write!(f, "{} ({})", name, self.id),
+            _ => write!(f, "{}", self.id),
+        }
+    }
+}
+
+/// How well a candidate matches, best first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum MatchRank {
+    Exact,
+    Prefix,
+    Substring,
+    /// Within a few typos, ranked by edit distance.
+    Fuzzy(usize),
+}
+
+/// Lowercases and drops everything but letters and digits, so "strike r" finds "Strike_R".
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|x| x.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn rank(needle: &str, text: &str) -> Option<MatchRank> {
+    let text = normalize(text);
+    if text == needle {
+        Some(MatchRank::Exact)
+    } else if text.starts_with(needle) {
+        Some(MatchRank::Prefix)
+    } else if text.contains(needle) {
+        Some(MatchRank::Substring)
+    } else {
+        let distance = strsim::levenshtein(needle, &text);
+        let allowed = (needle.chars().count() / 4).max(1);
+        if distance <= allowed {
+            Some(MatchRank::Fuzzy(distance))
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns the candidates that match `needle` best: all exact matches if there are any,
+/// otherwise all prefix matches, then substring matches, then the closest typos.
+/// Ids and display names are both matched and every id appears at most once.
+pub fn find_matches<'a>(needle: &str, candidates: &'a [Candidate]) -> Vec<&'a Candidate> {
+    let needle = normalize(needle);
+    if needle.is_empty() {
+        return Vec::new();
+    }
+    let mut ranked: Vec<(MatchRank, &Candidate)> = Vec::new();
+    for candidate in candidates {
+        if ranked.iter().any(|(_, x)| x.id == candidate.id) {
+            continue;
+        }
+        let best = std::iter::once(&candidate.id)
+            .chain(&candidate.name)
+            .filter_map(|x| rank(&needle, x))
+            .min();
+        if let Some(best) = best {
+            ranked.push((best, candidate));
+        }
+    }
+    let Some(best_rank) = ranked.iter().map(|(rank, _)| *rank).min() else {
+        return Vec::new();
+    };
+    let keep_tier = |rank: MatchRank| match (rank, best_rank) {
+        (MatchRank::Fuzzy(_), MatchRank::Fuzzy(_)) => true,
+        _ => rank == best_rank,
+    };
+    ranked.retain(|(rank, _)| keep_tier(*rank));
+    ranked.sort_by_key(|(rank, _)| *rank);
+    ranked.into_iter().map(|(_, candidate)| candidate).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates() -> Vec<Candidate> {
+        [
+            ("Strike_R", Some("Strike")),
+            ("Strike_G", Some("Strike")),
+            ("Searing Blow", Some("Searing Blow")),
+            ("Bash", Some("Bash")),
+            ("Bashful", None),
+            ("ModCard", None),
+        ]
+        .iter()
+        .map(|(id, name)| Candidate {
+            id: id.to_string(),
+            name: name.map(str::to_owned),
+        })
+        .collect()
+    }
+
+    fn ids(needle: &str, candidates: &[Candidate]) -> Vec<String> {
+        find_matches(needle, candidates)
+            .into_iter()
+            .map(|x| x.id.clone())
+            .collect()
+    }
+
+    #[test]
+    fn exact_matches_win_over_prefixes() {
+        assert_eq!(ids("bash", &candidates()), ["Bash"]);
+    }
+
+    #[test]
+    fn names_shared_by_cards_match_all_of_them() {
+        assert_eq!(ids("Strike", &candidates()), ["Strike_R", "Strike_G"]);
+    }
+
+    #[test]
+    fn separators_and_case_are_ignored() {
+        assert_eq!(ids("strike r", &candidates()), ["Strike_R"]);
+        assert_eq!(ids("SEARINGBLOW", &candidates()), ["Searing Blow"]);
+    }
+
+    #[test]
+    fn falls_back_to_prefix_substring_and_typos() {
+        assert_eq!(ids("sear", &candidates()), ["Searing Blow"]);
+        assert_eq!(ids("blow", &candidates()), ["Searing Blow"]);
+        assert_eq!(ids("searing blwo", &candidates()), ["Searing Blow"]);
+        assert_eq!(ids("modcad", &candidates()), ["ModCard"]);
+    }
+
+    #[test]
+    fn unrelated_and_empty_text_match_nothing() {
+        assert!(ids("apotheosis", &candidates()).is_empty());
+        assert!(ids(" _ ", &candidates()).is_empty());
+    }
+
+    #[test]
+    fn ids_listed_twice_match_once() {
+        let mut doubled = candidates();
+        doubled.extend(candidates());
+        assert_eq!(ids("bash", &doubled), ["Bash"]);
+    }
+
+    #[test]
+    fn shows_names_that_differ_from_the_id() {
+        let candidates = candidates();
+        assert_eq!(candidates[0].to_string(), "Strike (Strike_R)");
+        assert_eq!(candidates[3].to_string(), "Bash");
+    }
+}
@@ -0,0 +1,197 @@
+use anyhow::{anyhow, Result as AnyResult};
+use json_patch::Patch;
+use serde_json::Value as JsonValue;
+
+use std::path::Path;
+
+use crate::cache::STSCache;
+use crate::error::SaveError;
+
+pub fn load_patch(patch_path: &Path) -> AnyResult<Patch> {
+    let contents = std::fs::read_to_string(patch_path)?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+/// Makes a patch that turns `before` into `after`.
+pub fn make_patch(before: &JsonValue, after: &JsonValue) -> Patch {
+    json_patch::diff(before, after)
+}
+
+/// Applies `patch` to a copy of `json_dict`; an operation failing leaves nothing applied.
+pub fn apply_patch(json_dict: &JsonValue, patch: &Patch) -> AnyResult<JsonValue> {
+    let mut patched = json_dict.clone();
+    json_patch::patch(&mut patched, patch)?;
+    Ok(patched)
+}
+
+/// The game reads `relic_counters` by relic position, so a patch that changes either list
+/// has to leave exactly one counter per relic.
+fn check_relic_counters(before: &JsonValue, after: &JsonValue) -> Result<(), SaveError> {
+    let touched = ["relics", "relic_counters"]
+        .iter()
+        .any(|x| before.get(x) != after.get(x));
+    let len = |field: &str| after.get(field).and_then(JsonValue::as_array).map(Vec::len);
+    match (len("relics"), len("relic_counters")) {
+        (Some(relics), Some(counters)) if touched && relics != counters => {
+            Err(SaveError::invalid(
+                "relic_counters",
+                &format!(
+                    "the patch leaves {} counter(s) for {} relic(s), add or remove counters together with relics",
+                    counters, relics
+                ),
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn card_ids(json_dict: &JsonValue) -> Vec<&str> {
+    json_dict
+        .get("cards")
+        .and_then(JsonValue::as_array)
+        .map(|cards| {
+            cards
+                .iter()
+                .filter_map(|card| card.get("id")?.as_str())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn relic_ids(json_dict: &JsonValue) -> Vec<&str> {
+    json_dict
+        .get("relics")
+        .and_then(JsonValue::as_array)
+        .map(|relics| relics.iter().filter_map(JsonValue::as_str).collect())
+        .unwrap_or_default()
+}
+
+/// Checks a save after `before` was patched into `after`: every card and relic it holds
+/// has to be known to `STSCache` or already be in `before`, like modded ones, and the
+/// relic counters have to line up with the relics. Reports all unknown ids at once.
+pub fn validate_patched(before: &JsonValue, after: &JsonValue, cache: &STSCache) -> AnyResult<()> {
+    let mut problems = Vec::new();
+    let cards_before = card_ids(before);
+    for id in card_ids(after) {
+        if !cards_before.contains(&id) && !cache.cards.iter().any(|x| x.id == id) {
+            problems.push(format!("cards: unknown card id '{}'", id));
+        }
+    }
+    let relics_before = relic_ids(before);
+    for id in relic_ids(after) {
+        if !relics_before.contains(&id) && !cache.relics.iter().any(|x| x.id == id) {
+            problems.push(format!("relics: unknown relic id '{}'", id));
+        }
+    }
+    if !problems.is_empty() {
+        return Err(anyhow!("Invalid JSON Patch:\n{}", problems.join("\n")));
+    }
+    check_relic_counters(before, after)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::{Card, Relic};
+    use crate::cache_enums::{CardColor, CardRarity, CardType, RelicTier};
+    use serde_json::json;
+
+    fn test_cache() -> STSCache {
+        STSCache {
+            cards: vec![Card {
+                rarity: CardRarity::BASIC,
+                color: CardColor::RED,
+                type_: CardType::ATTACK,
+                misc: 0,
+                uses_misc: false,
+                max_upgrades: Some(1),
+                id: "Strike_R".to_owned(),
+                name: None,
+            }],
+            relics: ["Burning Blood", "Anchor"]
+                .iter()
+                .map(|id| Relic {
+                    tier: RelicTier::COMMON,
+                    id: id.to_string(),
+                    name: None,
+                })
+                .collect(),
+            potions: Vec::new(),
+        }
+    }
+
+    fn test_save() -> JsonValue {
+        json!({
+            "loadout": "IRONCLAD",
+            "cards": [{"id": "Strike_R", "upgrades": 0, "misc": 0}],
+            "relics": ["Burning Blood", "ModRelic"],
+            "relic_counters": [-1, 2]
+        })
+    }
+
+    fn check(patch: JsonValue) -> AnyResult<JsonValue> {
+        let patch: Patch = serde_json::from_value(patch).unwrap();
+        let before = test_save();
+        let after = apply_patch(&before, &patch)?;
+        validate_patched(&before, &after, &test_cache())?;
+        Ok(after)
+    }
+
+    #[test]
+    fn known_ids_can_be_added_and_replaced() {
+        let after = check(json!([
+            {"op": "add", "path": "/cards/-", "value": {"id": "Strike_R", "upgrades": 1, "misc": 0}},
+            {"op": "replace", "path": "/relics/0", "value": "Anchor"}
+        ]))
+        .unwrap();
+        assert_eq!(after["relics"], json!(["Anchor", "ModRelic"]));
+    }
+
+    #[test]
+    fn unknown_ids_are_rejected_whichever_op_writes_them() {
+        let patches = [
+            json!([{"op": "add", "path": "/cards/0", "value": {"id": "Bogus", "upgrades": 0, "misc": 0}}]),
+            json!([{"op": "replace", "path": "/cards/0/id", "value": "Bogus"}]),
+            json!([{"op": "copy", "from": "/loadout", "path": "/relics/0"}, {"op": "add", "path": "/relic_counters/-", "value": -1}]),
+            json!([{"op": "add", "path": "/x", "value": "Bogus"}, {"op": "move", "from": "/x", "path": "/relics/1"}]),
+        ];
+        for patch in patches {
+            let error = check(patch.clone()).unwrap_err().to_string();
+            assert!(
+                error.starts_with("Invalid JSON Patch"),
+                "{}: {}",
+                patch,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn replacing_the_whole_save_is_checked_too() {
+        let error = check(json!([{"op": "replace", "path": "", "value": {
+            "cards": [{"id": "TotallyBogusCard", "upgrades": 0, "misc": 0}],
+            "relics": ["NoSuchRelic"],
+            "relic_counters": [-1]
+        }}]))
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("'TotallyBogusCard'"));
+        assert!(error.contains("'NoSuchRelic'"));
+    }
+
+    #[test]
+    fn ids_the_save_already_had_are_kept() {
+        check(json!([{"op": "move", "from": "/relics/1", "path": "/relics/0"}])).unwrap();
+    }
+
+    #[test]
+    fn relic_counters_have_to_line_up() {
+        assert!(check(json!([{"op": "add", "path": "/relics/-", "value": "Anchor"}])).is_err());
+        check(json!([
+            {"op": "add", "path": "/relics/-", "value": "Anchor"},
+            {"op": "add", "path": "/relic_counters/-", "value": -1}
+        ]))
+        .unwrap();
+    }
+}
@@ -1,55 +1,87 @@
-use anyhow::Result as AnyResult;
-use base64;
-use rand::Rng;
-use serde::{Deserialize, Serialize};
+use anyhow::{anyhow, Context, Result as AnyResult};
+use json_patch::Patch;
 use serde_json::Value as JsonValue;
 
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use crate::cache::{Card, STSCache};
-use crate::cache_enums::CardColor;
+use crate::backup;
+use crate::cache::STSCache;
+use crate::cache_enums::{CardColor, RelicTier};
+use crate::codec::{self, Codec, SaveFormat};
+use crate::diff::SaveDiff;
+use crate::edits::Edit;
+use crate::history::History;
+use crate::matching::{self, Candidate};
+use crate::patch;
+use crate::save_file::{CardSave, SaveFile};
+use crate::script::EditScript;
+use crate::status::SaveStatus;
 
-const ENCODING_KEY: &[u8] = b"key";
-
-#[derive(Serialize, Deserialize, Debug)]
-struct JsonCard {
-    id: String,
-    misc: u32,
-    upgrades: u32,
+pub struct EditorOptions {
+    pub codec: Codec,
+    /// How many timestamped backups of the save to keep around.
+    pub backup_count: usize,
+    /// Only show what would change instead of writing the save.
+    pub dry_run: bool,
 }
 
-fn encode(data: &[u8], key: &[u8]) -> Vec<u8> {
-    let mut result = Vec::from(data);
-    let key_len = key.len();
-    result
-        .iter_mut()
-        .enumerate()
-        .for_each(|(i, v)| *v ^= key[i % key_len]);
-    result
+impl Default for EditorOptions {
+    fn default() -> Self {
+        EditorOptions {
+            codec: Codec::default(),
+            backup_count: backup::DEFAULT_BACKUP_COUNT,
+            dry_run: false,
+        }
+    }
 }
 
-pub fn get_save_file_path(folder: &PathBuf) -> Option<PathBuf> {
-    let args: Vec<_> = std::env::args().collect();
-    if args.len() <= 1 {
+/// Finds the save to work on, either `arg` or the only `.autosave` file in `folder`.
+///
+/// When several saves are found the user is asked to pick one if `prompt` is set,
+/// otherwise this fails and asks for `--save`.
+pub fn get_save_file_path(
+    folder: &Path,
+    arg: Option<PathBuf>,
+    prompt: bool,
+) -> AnyResult<Option<PathBuf>> {
+    if let Some(arg) = arg {
+        if arg.exists() {
+            Ok(Some(arg))
+        } else {
+            let arg_filename = folder.join(arg);
+            if arg_filename.exists() {
+                Ok(Some(arg_filename))
+            } else {
+                Ok(None)
+            }
+        }
+    } else {
         let mut save_file_names = Vec::with_capacity(5);
-        for entry_result in std::fs::read_dir(&folder).ok()? {
-            if let Ok(entry) = entry_result {
-                if let Ok(file_data) = entry.metadata() {
-                    if file_data.is_file() {
-                        let filename = entry.path();
-                        if let Some(extension) = filename.extension() {
-                            if extension == "autosave" {
-                                save_file_names.push(filename);
-                            }
+        let entries = match std::fs::read_dir(folder) {
+            Ok(entries) => entries,
+            Err(_) => return Ok(None),
+        };
+        for entry in entries.flatten() {
+            if let Ok(file_data) = entry.metadata() {
+                if file_data.is_file() {
+                    let filename = entry.path();
+                    if let Some(extension) = filename.extension() {
+                        if extension == "autosave" {
+                            save_file_names.push(filename);
                         }
                     }
                 }
-            };
+            }
         }
+        save_file_names.sort();
         match save_file_names.len() {
-            0 => None,
-            1 => Some(folder.join(save_file_names.pop().unwrap())),
+            0 => Ok(None),
+            1 => Ok(Some(folder.join(save_file_names.pop().unwrap()))),
+            _ if !prompt => Err(anyhow!(
+                "Several saves found ({}), pass --save",
+                save_file_names.len()
+            )),
             _ => {
                 println!("Found several save files, select one:");
                 let mut buffer = String::with_capacity(5);
@@ -58,221 +90,595 @@ pub fn get_save_file_path(folder: &PathBuf) -> Option<PathBuf> {
                     for (index, file) in save_file_names.iter().enumerate() {
                         println!("{}: {:?}", index + 1, file);
                     }
-                    std::io::stdin().read_line(&mut buffer).ok()?;
-                    if let Ok(value) = usize::from_str_radix(buffer.trim(), 10) {
+                    match std::io::stdin().read_line(&mut buffer) {
+                        Ok(0) | Err(_) => return Ok(None),
+                        Ok(_) => {}
+                    }
+                    if let Ok(value) = buffer.trim().parse::<usize>() {
                         if value > 0 && value <= save_file_names.len() {
                             break value - 1;
                         }
                     }
                 };
-                Some(folder.join(save_file_names[choice].clone()))
-            }
-        }
-    } else {
-        let arg = PathBuf::from(args.last().unwrap());
-        if arg.exists() {
-            Some(arg)
-        } else {
-            let arg_filename = folder.join(arg);
-            if arg_filename.exists() {
-                Some(arg_filename)
-            } else {
-                None
+                Ok(Some(folder.join(save_file_names[choice].clone())))
             }
         }
     }
 }
 
-fn unpack_file(savefile_path: &PathBuf) -> AnyResult<JsonValue> {
-    let contents = std::fs::read(&savefile_path)?;
-    let config = base64::Config::new(base64::CharacterSet::Standard, true);
-    let unbased_encoded = base64::decode_config(&contents, config)?;
-    let unbased_decoded = encode(&unbased_encoded, ENCODING_KEY);
-    let json_dict: JsonValue = serde_json::from_slice(&unbased_decoded)?;
-    Ok(json_dict)
+fn unpack_file(savefile_path: &Path, codec: &Codec) -> AnyResult<(JsonValue, SaveFormat)> {
+    let contents = std::fs::read(savefile_path)?;
+    codec.decode_save(&contents)
+}
+
+/// Backs up the save and replaces it with `value`, returning where the backup went.
+pub(crate) fn pack_file(
+    value: JsonValue,
+    format: &SaveFormat,
+    filepath: &Path,
+    options: &EditorOptions,
+) -> AnyResult<Option<PathBuf>> {
+    let contents = options.codec.encode_save(&value, format);
+    let backup_path = backup::make_timestamped_backup(filepath, options.backup_count)?;
+    backup::write_atomic(filepath, &contents)?;
+    Ok(backup_path)
+}
+
+pub fn load_save(save_file: &Path, codec: &Codec) -> AnyResult<(SaveFile, SaveFormat)> {
+    let (json_dict, format) = unpack_file(save_file, codec)?;
+    let save = SaveFile::from_json(json_dict)
+        .with_context(|| format!("Failed to load save '{:?}'", save_file))?;
+    Ok((save, format))
 }
 
-fn pack_file(value: JsonValue, filepath: &PathBuf) -> AnyResult<()> {
-    let config = base64::Config::new(base64::CharacterSet::Standard, true);
-    let json_string = value.to_string();
-    let unbased_encoded = encode(&json_string.as_bytes(), ENCODING_KEY);
-    let based_encoded = base64::encode_config(&unbased_encoded, config);
-    std::fs::write(&filepath, based_encoded).map_err(anyhow::Error::msg)
+/// Prints what `save` changes compared to the save currently on disk.
+fn print_pending_changes(
+    save_file: &Path,
+    cache: &STSCache,
+    options: &EditorOptions,
+    save: &SaveFile,
+) -> AnyResult<()> {
+    let (on_disk, _) = load_save(save_file, &options.codec)?;
+    println!("Pending changes:");
+    print!("{}", SaveDiff::between(&on_disk, save, cache)?);
+    Ok(())
+}
+
+/// Writes the edited save, or in dry-run mode only previews the changes. Nothing
+/// happens while the save still matches `original`, the JSON last read from or
+/// written to disk, so an unedited save keeps its contents and mtime.
+fn write_save(
+    save_file: &Path,
+    cache: &STSCache,
+    options: &EditorOptions,
+    save: &SaveFile,
+    format: &SaveFormat,
+    original: &JsonValue,
+) -> AnyResult<()> {
+    if save.to_json()? == *original {
+        println!("No changes, the save was left untouched.");
+        return Ok(());
+    }
+    if options.dry_run {
+        print_pending_changes(save_file, cache, options, save)?;
+        println!("Dry run, the save was not written.");
+        return Ok(());
+    }
+    if let Some(backup_path) = pack_file(save.to_json()?, format, save_file, options)? {
+        println!("Backed up the save to {:?}", backup_path);
+    }
+    Ok(())
 }
 
-fn print_status(json: &JsonValue) {
-    println!("\n=====StS SAVE STATUS=====");
-    println!("Gold: {} ({} gained)", json["gold"], json["gold_gained"]);
+pub fn verify_roundtrip(save_file: &Path, codec: &Codec) -> AnyResult<bool> {
+    let contents = std::fs::read(save_file)?;
+    match codec.find_roundtrip_mismatch(&contents)? {
+        None => {
+            println!(
+                "Round trip OK: {} bytes reproduced exactly.",
+                contents.len()
+            );
+            Ok(true)
+        }
+        Some(position) => {
+            println!(
+                "Round trip FAILED: output differs from the original at byte {} of {}.",
+                position,
+                contents.len()
+            );
+            Ok(false)
+        }
+    }
+}
 
-    let cards = serde_json::from_value::<Vec<JsonCard>>(json["cards"].clone()).unwrap();
-    let cards_ids: Vec<String> = cards.into_iter().map(|x| x.id).collect();
-    println!("Cards ({}): {:?}", cards_ids.len(), cards_ids);
+pub fn print_save(save: &SaveFile) {
+    print!("{}", SaveStatus::new(save));
+}
 
-    let relics = serde_json::from_value::<Vec<String>>(json["relics"].clone()).unwrap();
-    println!("Relics: {:?}", relics);
+fn print_status(save: &SaveFile, dirty: bool) {
+    if dirty {
+        println!("\n=====StS SAVE STATUS (unsaved changes)=====");
+    } else {
+        println!("\n=====StS SAVE STATUS=====");
+    }
+    print_save(save);
 
-    println!("Select action ('q' to quit):");
+    println!("Select action ('q' to quit, 'Q' to quit without saving):");
+    println!("w - Write the changes to the save");
     println!("g - Give 100 gold");
+    println!("hp - Change current or max HP");
     println!("z - Remove all cards");
     println!("x - Give 10 random cards");
     println!("c - Give 5 Colorless cards");
     println!("v/b/n/m - Give 5 Red/Green/Blue/Purple cards");
     println!("f - Give card by name");
     println!("r - Remove card by name");
+    println!("cu/cd - Upgrade/downgrade a card of the deck");
+    println!("ca - Upgrade every card that can be upgraded");
+    println!("cm - Change the misc value of a card, like Genetic Algorithm's block");
+    println!("ra/rr - Give/remove relic by name");
+    println!("rt - Give a random relic of a tier");
+    println!("ps - Put a potion into a slot");
+    println!("pf/pc - Fill empty potion slots with random potions/empty all potion slots");
+    println!("pn - Change the number of potion slots");
+    println!("p - Export this session's changes as a JSON Patch");
+    println!("preview - Show pending changes against the save on disk");
+    println!("u/U - Undo/redo the last edit");
+    println!("h - Show the edit history and go back to any point of it");
 }
 
-fn get_random_cards(
-    cache: &STSCache,
-    json_dict: &JsonValue,
-    rng: &mut rand::rngs::ThreadRng,
-    amount: u32,
-    filter: impl Fn(&Card) -> bool,
-) -> JsonValue {
-    let mut current_cards =
-        serde_json::from_value::<Vec<JsonCard>>(json_dict["cards"].clone()).unwrap();
-    for _ in 0..amount {
-        let random_card = loop {
-            let random_card_id = rng.gen_range(0, cache.cards.len());
-            let random_card_data = &cache.cards[random_card_id];
-            if !filter(random_card_data) {
-                continue;
-            } else {
-                break JsonCard {
-                    id: random_card_data.id.clone(),
-                    misc: random_card_data.misc,
-                    upgrades: 0,
-                };
+/// Asks for a card or relic (`what`) until the text picks exactly one of `possible`.
+fn get_name_from_user(what: &str, possible: &[Candidate]) -> AnyResult<Option<String>> {
+    let mut buffer = String::with_capacity(10);
+    let mut results: Vec<&Candidate> = Vec::new();
+    loop {
+        if results.is_empty() {
+            print!("Enter the name of {} (or nothing to leave): ", what);
+        } else {
+            print!("Enter a number, another name (or nothing to leave): ");
+        }
+        std::io::stdout().flush()?;
+        buffer.clear();
+        std::io::stdin().read_line(&mut buffer)?;
+        let needle = buffer.trim();
+        if needle.is_empty() {
+            break Ok(None);
+        }
+        if let Ok(choice) = needle.parse::<usize>() {
+            if choice > 0 && choice <= results.len() {
+                let choice = results[choice - 1];
+                println!("Picked {} {}.", what, choice);
+                break Ok(Some(choice.id.clone()));
             }
-        };
-        current_cards.push(random_card);
+        }
+
+        results = matching::find_matches(needle, possible);
+        match results.len() {
+            0 => println!("No {} matches that text.", what),
+            1 => {
+                let choice = results[0];
+                println!("Matched {} {}.", what, choice);
+                break Ok(Some(choice.id.clone()));
+            }
+            _ => {
+                println!("Found several matches:");
+                for (index, choice) in results.iter().enumerate() {
+                    println!("{:>3}: {}", index + 1, choice);
+                }
+            }
+        }
+    }
+}
+
+fn get_relic_tier_from_user(cache: &STSCache) -> AnyResult<Option<Option<RelicTier>>> {
+    let mut tiers: Vec<RelicTier> = Vec::new();
+    for relic in &cache.relics {
+        if !tiers.contains(&relic.tier) {
+            tiers.push(relic.tier);
+        }
+    }
+    let mut buffer = String::with_capacity(10);
+    loop {
+        print!(
+            "Enter a relic tier {:?}, 'any' (or nothing to leave): ",
+            tiers
+        );
+        std::io::stdout().flush()?;
+        buffer.clear();
+        std::io::stdin().read_line(&mut buffer)?;
+        let text = buffer.trim().to_uppercase();
+        if text.is_empty() {
+            break Ok(None);
+        }
+        if text == "ANY" {
+            break Ok(Some(None));
+        }
+        match RelicTier::from_str(&text) {
+            Some(tier) => break Ok(Some(Some(tier))),
+            None => println!("Unknown relic tier '{}'.", text),
+        }
     }
-    serde_json::to_value(current_cards).unwrap()
 }
 
-fn add_specific_card(cache: &STSCache, json_dict: &JsonValue, card_name: &str) -> JsonValue {
-    let mut current_cards =
-        serde_json::from_value::<Vec<JsonCard>>(json_dict["cards"].clone()).unwrap();
-    for card in &cache.cards {
-        if card.id == card_name {
-            let new_card = JsonCard {
-                id: card.id.clone(),
-                misc: card.misc,
-                upgrades: 0,
-            };
-            current_cards.push(new_card);
+pub fn recover_key(save_file: &Path) -> AnyResult<Vec<u8>> {
+    let contents = std::fs::read(save_file)?;
+    let key = codec::recover_key(&contents)?;
+    match std::str::from_utf8(&key) {
+        Ok(text_key) => println!("Recovered key: {:?}", text_key),
+        Err(_) => {
+            let hex: String = key.iter().map(|x| format!("{:02x}", x)).collect();
+            println!("Recovered key: hex:{}", hex);
         }
     }
-    serde_json::to_value(current_cards).unwrap()
+    Ok(key)
 }
 
-fn remove_specific_card(json_dict: &JsonValue, card_name: &str) -> JsonValue {
-    let mut current_cards =
-        serde_json::from_value::<Vec<JsonCard>>(json_dict["cards"].clone()).unwrap();
-    current_cards.retain(|x| x.id != card_name);
-    serde_json::to_value(current_cards).unwrap()
+/// Turns "50", "50/80", "/80", "+10", "-5" or "full" into an HP edit.
+fn parse_health(text: &str, record: bool) -> Option<Edit> {
+    let number = |x: &str| x.trim().parse::<i32>().ok();
+    if text == "full" {
+        Some(Edit::Heal { record })
+    } else if text.starts_with('+') || text.starts_with('-') {
+        Some(Edit::AddHealth {
+            current: number(text)?,
+            max: 0,
+            record,
+        })
+    } else if let Some((current, max)) = text.split_once('/') {
+        let current = if current.trim().is_empty() {
+            None
+        } else {
+            Some(number(current)?)
+        };
+        Some(Edit::SetHealth {
+            current,
+            max: Some(number(max)?),
+            record,
+        })
+    } else {
+        Some(Edit::SetHealth {
+            current: Some(number(text)?),
+            max: None,
+            record,
+        })
+    }
 }
 
-fn get_card_name_from_user(possible_cards: &[String]) -> Option<String> {
+fn get_health_edit_from_user(save: &SaveFile) -> AnyResult<Option<Edit>> {
     let mut buffer = String::with_capacity(10);
-    let mut results: Vec<_> = Vec::with_capacity(10);
+    let text = loop {
+        println!("HP: {}/{}", save.current_health, save.max_health);
+        print!("Enter 'current', 'current/max', '/max', '+N' or '-N' to add, or 'full' (or nothing to leave): ");
+        std::io::stdout().flush()?;
+        buffer.clear();
+        std::io::stdin().read_line(&mut buffer)?;
+        let text = buffer.trim();
+        if text.is_empty() {
+            return Ok(None);
+        }
+        if parse_health(text, false).is_some() {
+            break text.to_owned();
+        }
+        println!("Could not read '{}' as HP.", text);
+    };
+    let mut record = false;
+    if save.metric_max_hp_per_floor.is_some() {
+        print!("Also change this floor's HP in the run history? (y/N): ");
+        std::io::stdout().flush()?;
+        buffer.clear();
+        std::io::stdin().read_line(&mut buffer)?;
+        record = buffer.trim().eq_ignore_ascii_case("y");
+    }
+    Ok(parse_health(&text, record))
+}
+
+fn get_number_from_user<T: std::str::FromStr>(prompt: &str) -> AnyResult<Option<T>> {
+    let mut buffer = String::with_capacity(5);
     loop {
-        print!("Enter the name of card (or nothing to leave): ");
-        std::io::stdout().flush().expect("Failed to flush stdout.");
+        print!("{} (or nothing to leave): ", prompt);
+        std::io::stdout().flush()?;
         buffer.clear();
-        results.clear();
-        std::io::stdin()
-            .read_line(&mut buffer)
-            .expect("Failed to read input into buffer in get_card_name_from_user.");
-        let lower_buffer = buffer.to_lowercase();
-        let needle = lower_buffer.trim();
-        if needle.is_empty() {
-            break None;
+        std::io::stdin().read_line(&mut buffer)?;
+        let text = buffer.trim();
+        if text.is_empty() {
+            break Ok(None);
+        }
+        match text.parse::<T>() {
+            Ok(number) => break Ok(Some(number)),
+            Err(_) => println!("'{}' is not a number.", text),
         }
+    }
+}
 
-        for choice in possible_cards {
-            if choice.to_lowercase().contains(needle) {
-                results.push(choice.clone());
+/// Lists the deck with positions and asks for one of them.
+fn get_deck_index_from_user(save: &SaveFile) -> AnyResult<Option<usize>> {
+    for (index, card) in SaveStatus::new(save).deck.iter().enumerate() {
+        println!("{:>3}: {}", index, card);
+    }
+    loop {
+        match get_number_from_user("Enter the position of the card")? {
+            Some(index) if index >= save.cards.len() => {
+                println!("There is no card #{} in the deck.", index)
             }
+            index => break Ok(index),
         }
-        match results.len() {
-            0 => {
-                println!("No card matches that text.");
-                continue;
-            }
-            1 => {
-                let card_name = results.pop().expect("Tried to pop from empty results.");
-                println!("Matched card '{}'.", card_name);
-                break Some(card_name);
-            }
-            _ => println!("Found several matches: {:?}", results),
+    }
+}
+
+/// The card's `misc` value next to the default the cache has for it.
+pub fn describe_misc(card: &CardSave, cache: &STSCache) -> String {
+    match cache.cards.iter().find(|x| x.id == card.id) {
+        Some(cached) if cached.uses_misc => {
+            format!("{}: misc {} (default {})", card.id, card.misc, cached.misc)
         }
+        Some(_) => format!("{}: misc {} (not used by this card)", card.id, card.misc),
+        None => format!("{}: misc {} (unknown card)", card.id, card.misc),
     }
 }
 
-pub fn process_file(save_file: &PathBuf, cache: &STSCache) -> AnyResult<()> {
-    let mut json_dict = unpack_file(save_file)?;
-    let mut buffer = String::with_capacity(5);
-    let mut rng = rand::thread_rng();
+fn get_misc_edit_from_user(save: &SaveFile, cache: &STSCache) -> AnyResult<Option<Edit>> {
+    let uses_misc = |card: &CardSave| cache.cards.iter().any(|x| x.id == card.id && x.uses_misc);
+    if !save.cards.iter().any(uses_misc) {
+        println!("No card of the deck uses misc.");
+        return Ok(None);
+    }
+    for (index, card) in save.cards.iter().enumerate().filter(|(_, x)| uses_misc(x)) {
+        println!("{:>3}: {}", index, describe_misc(card, cache));
+    }
+    let index = loop {
+        match get_number_from_user("Enter the position of the card")? {
+            Some(index) if save.cards.get(index).is_some_and(uses_misc) => break index,
+            Some(index) => println!("Card #{} does not use misc.", index),
+            None => return Ok(None),
+        }
+    };
+    Ok(get_number_from_user("Enter the new misc value")?
+        .map(|misc| Edit::SetCardMisc { index, misc }))
+}
+
+fn get_potion_edit_from_user(cache: &STSCache) -> AnyResult<Option<Edit>> {
+    let Some(slot) = get_number_from_user("Enter the potion slot, counted from 0")? else {
+        return Ok(None);
+    };
+    let potions = cache.potion_candidates(cache.potions.iter().map(|x| &x.id));
+    Ok(get_name_from_user("potion", &potions)?.map(|id| Edit::SetPotion { slot, id }))
+}
+
+fn random_cards(count: usize, color: CardColor) -> Option<Edit> {
+    Some(Edit::AddRandomCards {
+        count,
+        color: Some(color),
+    })
+}
+
+fn get_edit_from_user(command: &str, save: &SaveFile, cache: &STSCache) -> AnyResult<Option<Edit>> {
+    Ok(match command {
+        "g" => Some(Edit::AddGold { amount: 100 }),
+        "hp" => get_health_edit_from_user(save)?,
+        "f" => get_name_from_user(
+            "card",
+            &cache.card_candidates(cache.cards.iter().map(|x| &x.id)),
+        )?
+        .map(|id| Edit::AddCard {
+            id,
+            upgrades: 0,
+            count: 1,
+        }),
+        "r" => get_name_from_user("card", &cache.card_candidates(&save.card_ids()))?
+            .map(|id| Edit::RemoveCard { id, count: None }),
+        "cu" => get_deck_index_from_user(save)?.map(|index| Edit::UpgradeCard { index }),
+        "cd" => get_deck_index_from_user(save)?.map(|index| Edit::DowngradeCard { index }),
+        "ca" => Some(Edit::UpgradeAllCards),
+        "cm" => get_misc_edit_from_user(save, cache)?,
+        "z" => Some(Edit::RemoveAllCards),
+        "x" => Some(Edit::AddRandomCards {
+            count: 10,
+            color: None,
+        }),
+        "v" => random_cards(5, CardColor::RED),
+        "b" => random_cards(5, CardColor::GREEN),
+        "n" => random_cards(5, CardColor::BLUE),
+        "m" => random_cards(5, CardColor::PURPLE),
+        "c" => random_cards(5, CardColor::COLORLESS),
+        "ra" => get_name_from_user(
+            "relic",
+            &cache.relic_candidates(cache.relics.iter().map(|x| &x.id)),
+        )?
+        .map(|id| Edit::AddRelic { id }),
+        "rr" => get_name_from_user("relic", &cache.relic_candidates(&save.relics))?
+            .map(|id| Edit::RemoveRelic { id }),
+        "ps" => get_potion_edit_from_user(cache)?,
+        "pf" => Some(Edit::FillPotions { id: None }),
+        "pc" => Some(Edit::ClearPotions { slot: None }),
+        "pn" => get_number_from_user("Enter the number of potion slots")?
+            .map(|count| Edit::SetPotionSlots { count }),
+        "rt" => {
+            get_relic_tier_from_user(cache)?.map(|tier| Edit::AddRandomRelics { count: 1, tier })
+        }
+        _ => None,
+    })
+}
+
+/// Loads the save, applies a single edit and writes the save back.
+pub fn apply_edit(
+    save_file: &Path,
+    cache: &STSCache,
+    options: &EditorOptions,
+    edit: &Edit,
+) -> AnyResult<()> {
+    let script = EditScript {
+        edits: vec![edit.clone()],
+    };
+    apply_script(save_file, cache, options, &script)
+}
 
-    let all_cache_card_ids: Vec<_> = cache.cards.iter().map(|x| x.id.clone()).collect();
+/// Loads the save, applies every edit of the script and writes the save back once,
+/// leaving the file untouched if any edit is invalid or fails.
+pub fn apply_script(
+    save_file: &Path,
+    cache: &STSCache,
+    options: &EditorOptions,
+    script: &EditScript,
+) -> AnyResult<()> {
+    script.validate(cache)?;
+    let (mut save, format) = load_save(save_file, &options.codec)?;
+    let original = save.to_json()?;
+    script.apply(&mut save, cache, &mut rand::thread_rng())?;
+    for edit in &script.edits {
+        println!("{}", edit);
+    }
+    write_save(save_file, cache, options, &save, &format, &original)
+}
+
+/// Applies a JSON Patch to the decoded save JSON and writes the save back, provided
+/// the patch only uses known ids and the result is still a loadable save.
+pub fn apply_patch(
+    save_file: &Path,
+    cache: &STSCache,
+    options: &EditorOptions,
+    json_patch: &Patch,
+) -> AnyResult<()> {
+    let (json_dict, format) = unpack_file(save_file, &options.codec)?;
+    let patched = patch::apply_patch(&json_dict, json_patch)?;
+    patch::validate_patched(&json_dict, &patched, cache)?;
+    let save = SaveFile::from_json(patched).context("The patched save is not valid")?;
+    println!("Applied {} patch operation(s).", json_patch.0.len());
+    write_save(save_file, cache, options, &save, &format, &json_dict)
+}
+
+/// Writes the changes made since the save was loaded as a JSON Patch next to it.
+fn export_session_patch(save_file: &Path, original: &JsonValue, save: &SaveFile) -> AnyResult<()> {
+    let session_patch = patch::make_patch(original, &save.to_json()?);
+    let mut patch_name = save_file.as_os_str().to_owned();
+    // Not ".json", which encrypt-dir would take for a decrypted save.
+    patch_name.push(".patch");
+    let patch_path = PathBuf::from(patch_name);
+    std::fs::write(&patch_path, serde_json::to_string_pretty(&session_patch)?)?;
+    println!(
+        "Wrote {} patch operation(s) to {:?}",
+        session_patch.0.len(),
+        patch_path
+    );
+    Ok(())
+}
+
+/// Shows the session history and lets the user pick a state to return to.
+fn get_history_index_from_user(history: &History) -> AnyResult<Option<usize>> {
+    let mut buffer = String::with_capacity(5);
     loop {
-        print_status(&json_dict);
+        history.print();
+        print!("Enter the number of the state to return to (or nothing to leave): ");
+        std::io::stdout().flush()?;
         buffer.clear();
         std::io::stdin().read_line(&mut buffer)?;
+        let choice = buffer.trim();
+        if choice.is_empty() {
+            break Ok(None);
+        }
+        match choice.parse::<usize>() {
+            Ok(index) if history.has_state(index) => break Ok(Some(index)),
+            _ => println!("No state with that number."),
+        }
+    }
+}
+
+/// Asks what to do with unsaved changes on quit: `Some(true)` to write them,
+/// `Some(false)` to discard them and `None` to keep editing.
+fn get_quit_choice_from_user() -> AnyResult<Option<bool>> {
+    let mut buffer = String::with_capacity(5);
+    loop {
+        print!("The save has unsaved changes. Write them? (y - write, n - discard, c - cancel): ");
+        std::io::stdout().flush()?;
+        buffer.clear();
+        if std::io::stdin().read_line(&mut buffer)? == 0 {
+            // Nobody is left to answer, keep the edits like quitting always did.
+            break Ok(Some(true));
+        }
         match buffer.trim() {
-            "g" => {
-                let g1 = serde_json::from_value::<u32>(json_dict["gold"].clone()).unwrap() + 100;
-                json_dict["gold"] = JsonValue::from(g1);
-                let g2 =
-                    serde_json::from_value::<u32>(json_dict["gold_gained"].clone()).unwrap() + 100;
-                json_dict["gold_gained"] = JsonValue::from(g2);
+            "y" => break Ok(Some(true)),
+            "n" => break Ok(Some(false)),
+            "c" => break Ok(None),
+            _ => println!("Please answer 'y', 'n' or 'c'."),
+        }
+    }
+}
+
+pub fn process_file(save_file: &Path, cache: &STSCache, options: &EditorOptions) -> AnyResult<()> {
+    let (mut save, format) = load_save(save_file, &options.codec)?;
+    println!("Save format: {}", format.encoding);
+    let original = save.to_json()?;
+    // The save as it is on disk, to tell whether there is anything to write.
+    let mut written = original.clone();
+    let mut history = History::new(save.clone());
+    let mut buffer = String::with_capacity(5);
+    let mut rng = rand::thread_rng();
+
+    loop {
+        let dirty = save.to_json()? != written;
+        print_status(&save, dirty);
+        buffer.clear();
+        let read = std::io::stdin().read_line(&mut buffer)?;
+        let command = buffer.trim();
+        if command == "Q" {
+            if dirty {
+                println!("Discarded unsaved changes.");
             }
-            "f" => {
-                if let Some(card_name) = get_card_name_from_user(&all_cache_card_ids) {
-                    json_dict["cards"] = add_specific_card(&cache, &json_dict, &card_name);
-                }
+            break;
+        }
+        if command == "q" || read == 0 {
+            if !dirty {
+                break;
             }
-            "r" => {
-                let cards = serde_json::from_value::<Vec<JsonCard>>(json_dict["cards"].clone()).unwrap();
-                let cards_ids: Vec<String> = cards.into_iter().map(|x| x.id).collect();
-                if let Some(card_name) = get_card_name_from_user(&cards_ids) {
-                    json_dict["cards"] = remove_specific_card(&json_dict, &card_name);
+            match get_quit_choice_from_user()? {
+                Some(true) => {
+                    write_save(save_file, cache, options, &save, &format, &written)?;
+                    break;
                 }
+                Some(false) => break,
+                None => continue,
             }
-            "z" => {
-                json_dict["cards"] = serde_json::to_value(Vec::<JsonCard>::new()).unwrap();
-            }
-            "x" => {
-                json_dict["cards"] = get_random_cards(&cache, &json_dict, &mut rng, 10, |_| true);
-            }
-            "v" => {
-                json_dict["cards"] = get_random_cards(&cache, &json_dict, &mut rng, 5, |x| {
-                    x.color == CardColor::RED
-                });
-            }
-            "b" => {
-                json_dict["cards"] = get_random_cards(&cache, &json_dict, &mut rng, 5, |x| {
-                    x.color == CardColor::GREEN
-                });
-            }
-            "n" => {
-                json_dict["cards"] = get_random_cards(&cache, &json_dict, &mut rng, 5, |x| {
-                    x.color == CardColor::BLUE
-                });
-            }
-            "m" => {
-                json_dict["cards"] = get_random_cards(&cache, &json_dict, &mut rng, 5, |x| {
-                    x.color == CardColor::PURPLE
-                });
+        }
+        let result = match command {
+            "w" => write_save(save_file, cache, options, &save, &format, &written).and_then(|()| {
+                if !options.dry_run {
+                    written = save.to_json()?;
+                }
+                Ok(())
+            }),
+            "p" => export_session_patch(save_file, &original, &save),
+            "preview" => print_pending_changes(save_file, cache, options, &save),
+            "u" => {
+                match history.undo() {
+                    Some(previous) => save = previous.clone(),
+                    None => println!("Nothing to undo."),
+                }
+                Ok(())
             }
-            "c" => {
-                json_dict["cards"] = get_random_cards(&cache, &json_dict, &mut rng, 5, |x| {
-                    x.color == CardColor::COLORLESS
-                });
+            "U" => {
+                match history.redo() {
+                    Some(next) => save = next.clone(),
+                    None => println!("Nothing to redo."),
+                }
+                Ok(())
             }
-            "q" => break,
-            _ => continue,
+            "h" => get_history_index_from_user(&history).map(|index| {
+                if let Some(state) = index.and_then(|x| history.go_to(x)) {
+                    save = state.clone();
+                }
+            }),
+            _ => get_edit_from_user(command, &save, cache).and_then(|edit| {
+                if let Some(edit) = edit {
+                    edit.apply(&mut save, cache, &mut rng)?;
+                    history.record(edit.to_string(), save.clone());
+                }
+                Ok(())
+            }),
+        };
+        if let Err(error) = result {
+            println!("Error: {:#}", error);
+            // Drop whatever a failed edit may have changed.
+            save = history.current().clone();
         }
     }
-    pack_file(json_dict, save_file)
+    Ok(())
 }
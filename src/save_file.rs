@@ -0,0 +1,130 @@
+use anyhow::Result as AnyResult;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+use crate::error::SaveError;
+
+/// One card of the deck, as stored by the game's `CardSave`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CardSave {
+    pub upgrades: i32,
+    pub misc: i32,
+    pub id: String,
+}
+
+/// The id the game keeps in potion slots that hold no potion.
+pub const EMPTY_POTION_SLOT: &str = "Potion Slot";
+
+/// The known fields of an autosave.
+///
+/// Fields the editor does not know about are kept in `extra`, and the original key
+/// order is remembered, so writing an unedited save back reproduces it exactly.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SaveFile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub loadout: Option<String>,
+    pub current_health: i32,
+    pub max_health: i32,
+    pub gold: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gold_gained: Option<i32>,
+    pub cards: Vec<CardSave>,
+    pub relics: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relic_counters: Option<Vec<i32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub potions: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub potion_slots: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_ruby_key: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_emerald_key: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_sapphire_key: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub floor_num: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub act_num: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ascension_level: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub boss: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metric_current_hp_per_floor: Option<Vec<i32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metric_max_hp_per_floor: Option<Vec<i32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metric_gold_per_floor: Option<Vec<i32>>,
+    #[serde(flatten)]
+    pub extra: JsonMap<String, JsonValue>,
+    #[serde(skip)]
+    key_order: Vec<String>,
+}
+
+impl SaveFile {
+    pub fn from_json(json_dict: JsonValue) -> Result<Self, SaveError> {
+        let key_order = match &json_dict {
+            JsonValue::Object(fields) => fields.keys().cloned().collect(),
+            _ => Vec::new(),
+        };
+        let mut save: SaveFile =
+            serde_path_to_error::deserialize(json_dict).map_err(SaveError::from_path_error)?;
+        save.key_order = key_order;
+        Ok(save)
+    }
+
+    /// Converts the save back to JSON, with the keys in the order they were loaded in.
+    pub fn to_json(&self) -> AnyResult<JsonValue> {
+        let mut fields = match serde_json::to_value(self)? {
+            JsonValue::Object(fields) => fields,
+            _ => unreachable!("SaveFile always serializes to a JSON object."),
+        };
+        let mut ordered = JsonMap::with_capacity(fields.len());
+        for key in &self.key_order {
+            if let Some(value) = fields.shift_remove(key) {
+                ordered.insert(key.clone(), value);
+            }
+        }
+        ordered.extend(fields);
+        Ok(JsonValue::Object(ordered))
+    }
+
+    pub fn card_ids(&self) -> Vec<String> {
+        self.cards.iter().map(|x| x.id.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAVE_JSON: &str = r#"{"relics":["Burning Blood"],"zeta":{"b":1,"a":[2,3]},"gold":99,"cards":[{"upgrades":0,"misc":0,"id":"Strike_R"}],"max_health":80,"alpha":"kept","current_health":60,"potion_slots":3,"metric_gold_per_floor":[99,120]}"#;
+
+    #[test]
+    fn unedited_saves_convert_back_unchanged() {
+        let json_dict: JsonValue = serde_json::from_str(SAVE_JSON).unwrap();
+        let save = SaveFile::from_json(json_dict.clone()).unwrap();
+        assert_eq!(save.extra.len(), 2);
+        let json = save.to_json().unwrap();
+        assert_eq!(json, json_dict);
+        assert_eq!(serde_json::to_string(&json).unwrap(), SAVE_JSON);
+    }
+
+    #[test]
+    fn new_fields_go_after_the_loaded_ones() {
+        let json_dict: JsonValue = serde_json::from_str(SAVE_JSON).unwrap();
+        let mut save = SaveFile::from_json(json_dict).unwrap();
+        save.gold_gained = Some(10);
+        let json = save.to_json().unwrap();
+        let keys: Vec<&String> = json.as_object().unwrap().keys().collect();
+        assert_eq!(keys.first().unwrap().as_str(), "relics");
+        assert_eq!(keys.last().unwrap().as_str(), "gold_gained");
+    }
+}
@@ -0,0 +1,168 @@
+use anyhow::{anyhow, Context, Result as AnyResult};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use std::path::Path;
+
+use crate::cache::STSCache;
+use crate::edits::Edit;
+use crate::save_file::SaveFile;
+
+/// An ordered list of edits read from a TOML, YAML or JSON file, applied all or nothing.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EditScript {
+    pub edits: Vec<Edit>,
+}
+
+impl EditScript {
+    /// Reads a script, picking the format from the file extension.
+    pub fn load(script_path: &Path) -> AnyResult<Self> {
+        let contents = std::fs::read_to_string(script_path)?;
+        let extension = script_path
+            .extension()
+            .map(|x| x.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let script = match extension.as_str() {
+            "toml" => toml::from_str(&contents)?,
+            "yaml" | "yml" => serde_yaml::from_str(&contents)?,
+            "json" => serde_json::from_str(&contents)?,
+            _ => {
+                return Err(anyhow!(
+                    "Unknown script format '{}', expected toml, yaml or json",
+                    extension
+                ))
+            }
+        };
+        Ok(script)
+    }
+
+    /// Checks every edit against `STSCache`, reporting all problems at once.
+    pub fn validate(&self, cache: &STSCache) -> AnyResult<()> {
+        let problems: Vec<String> = self
+            .edits
+            .iter()
+            .enumerate()
+            .filter_map(|(index, edit)| {
+                edit.validate(cache)
+                    .err()
+                    .map(|error| format!("step {} ({}): {}", index + 1, edit, error))
+            })
+            .collect();
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("Invalid edit script:\n{}", problems.join("\n")))
+        }
+    }
+
+    /// Applies every edit to a copy of `save`, so `save` is only changed if all of them succeed.
+    pub fn apply(
+        &self,
+        save: &mut SaveFile,
+        cache: &STSCache,
+        rng: &mut impl Rng,
+    ) -> AnyResult<()> {
+        let mut edited = save.clone();
+        for (index, edit) in self.edits.iter().enumerate() {
+            edit.apply(&mut edited, cache, rng)
+                .with_context(|| format!("Step {} ({}) failed", index + 1, edit))?;
+        }
+        *save = edited;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::Relic;
+    use crate::cache_enums::RelicTier;
+    use crate::codec::Codec;
+    use crate::save_editor::{self, EditorOptions};
+    use std::path::PathBuf;
+
+    fn temp_folder(name: &str) -> PathBuf {
+        let folder =
+            std::env::temp_dir().join(format!("stsse-script-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&folder);
+        std::fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    fn test_cache() -> STSCache {
+        STSCache {
+            cards: Vec::new(),
+            relics: ["Burning Blood", "Anchor"]
+                .iter()
+                .map(|id| Relic {
+                    tier: RelicTier::COMMON,
+                    id: id.to_string(),
+                    name: None,
+                })
+                .collect(),
+            potions: Vec::new(),
+        }
+    }
+
+    fn expected_script() -> EditScript {
+        EditScript {
+            edits: vec![
+                Edit::AddGold { amount: 50 },
+                Edit::RemoveRelic {
+                    id: "Anchor".to_owned(),
+                },
+            ],
+        }
+    }
+
+    fn load(file_name: &str, contents: &str) -> AnyResult<EditScript> {
+        let folder = temp_folder(file_name);
+        let script_path = folder.join(file_name);
+        std::fs::write(&script_path, contents).unwrap();
+        let script = EditScript::load(&script_path);
+        std::fs::remove_dir_all(&folder).unwrap();
+        script
+    }
+
+    #[test]
+    fn scripts_load_from_every_format() {
+        let toml = r#"
+            [[edits]]
+            op = "add_gold"
+            amount = 50
+
+            [[edits]]
+            op = "remove_relic"
+            id = "Anchor"
+        "#;
+        let yaml =
+            "edits:\n  - op: add_gold\n    amount: 50\n  - op: remove_relic\n    id: Anchor\n";
+        let json = r#"{"edits": [{"op": "add_gold", "amount": 50}, {"op": "remove_relic", "id": "Anchor"}]}"#;
+        assert_eq!(load("edits.toml", toml).unwrap(), expected_script());
+        assert_eq!(load("edits.yaml", yaml).unwrap(), expected_script());
+        assert_eq!(load("edits.json", json).unwrap(), expected_script());
+        assert!(load("edits.txt", json).is_err());
+    }
+
+    #[test]
+    fn failing_steps_leave_the_save_untouched() {
+        let folder = temp_folder("failing");
+        let save_path = folder.join("IRONCLAD.autosave");
+        let json_text = r#"{"current_health":60,"max_health":80,"gold":99,"cards":[],"relics":["Burning Blood"]}"#;
+        let contents = Codec::default().encrypt(json_text.as_bytes());
+        std::fs::write(&save_path, &contents).unwrap();
+
+        // Both steps pass validation, but the save does not own the relic to remove.
+        let result = save_editor::apply_script(
+            &save_path,
+            &test_cache(),
+            &EditorOptions::default(),
+            &expected_script(),
+        );
+
+        assert!(result.is_err());
+        assert_eq!(std::fs::read(&save_path).unwrap(), contents);
+        assert_eq!(std::fs::read_dir(&folder).unwrap().count(), 1);
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,264 @@
+use serde::Serialize;
+
+use crate::save_file::SaveFile;
+
+/// Bumped whenever a field of `SaveStatus` is renamed, removed or changes meaning.
+pub const STATUS_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DeckCard {
+    pub id: String,
+    pub upgrades: i32,
+    pub misc: i32,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct OwnedRelic {
+    pub id: String,
+    /// `None` when the save has no counter for this relic.
+    pub counter: Option<i32>,
+}
+
+/// A summary of a save for people and scripts, with a stable schema.
+///
+/// Fields the save lacks are `null` in JSON rather than left out.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SaveStatus {
+    pub schema_version: u32,
+    pub character: Option<String>,
+    pub gold: i32,
+    pub gold_gained: Option<i32>,
+    pub current_health: i32,
+    pub max_health: i32,
+    pub floor: Option<i32>,
+    pub act: Option<i32>,
+    pub ascension_level: Option<i32>,
+    pub deck: Vec<DeckCard>,
+    pub relics: Vec<OwnedRelic>,
+    pub potions: Vec<String>,
+    pub potion_slots: Option<i32>,
+}
+
+impl SaveStatus {
+    pub fn new(save: &SaveFile) -> Self {
+        let counters = save.relic_counters.as_deref().unwrap_or_default();
+        SaveStatus {
+            schema_version: STATUS_SCHEMA_VERSION,
+            character: save.loadout.clone(),
+            gold: save.gold,
+            gold_gained: save.gold_gained,
+            current_health: save.current_health,
+            max_health: save.max_health,
+            floor: save.floor_num,
+            act: save.act_num,
+            ascension_level: save.ascension_level,
+            deck: save
+                .cards
+                .iter()
+                .map(|x| DeckCard {
+                    id: x.id.clone(),
+                    upgrades: x.upgrades,
+                    misc: x.misc,
+                })
+                .collect(),
+            relics: save
+                .relics
+                .iter()
+                .enumerate()
+                .map(|(index, id)| OwnedRelic {
+                    id: id.clone(),
+                    counter: counters.get(index).copied(),
+                })
+                .collect(),
+            potions: save.potions.clone().unwrap_or_default(),
+            potion_slots: save.potion_slots,
+        }
+    }
+
+    /// Tab separated rows whose first column says what the row is:
+    ///
+    /// - `stat <name> <value>`, skipped when the save lacks the value
+    /// - `card <id> <upgrades> <misc>`, one row per card of the deck
+    /// - `relic <id> <counter>`, with an empty counter when there is none
+    /// - `potion <slot> <id>`
+    pub fn to_tsv(&self) -> String {
+        let mut rows = Vec::new();
+        let stats = [
+            ("character", self.character.clone()),
+            ("gold", Some(self.gold.to_string())),
+            ("gold_gained", self.gold_gained.map(|x| x.to_string())),
+            ("current_health", Some(self.current_health.to_string())),
+            ("max_health", Some(self.max_health.to_string())),
+            ("floor", self.floor.map(|x| x.to_string())),
+            ("act", self.act.map(|x| x.to_string())),
+            (
+                "ascension_level",
+                self.ascension_level.map(|x| x.to_string()),
+            ),
+            ("potion_slots", self.potion_slots.map(|x| x.to_string())),
+        ];
+        for (name, value) in stats {
+            if let Some(value) = value {
+                rows.push(format!("stat\t{}\t{}", name, value));
+            }
+        }
+        for card in &self.deck {
+            rows.push(format!(
+                "card\t{}\t{}\t{}",
+                card.id, card.upgrades, card.misc
+            ));
+        }
+        for relic in &self.relics {
+            let counter = relic.counter.map(|x| x.to_string()).unwrap_or_default();
+            rows.push(format!("relic\t{}\t{}", relic.id, counter));
+        }
+        for (slot, potion) in self.potions.iter().enumerate() {
+            rows.push(format!("potion\t{}\t{}", slot, potion));
+        }
+        rows.iter().map(|x| format!("{}\n", x)).collect()
+    }
+}
+
+impl std::fmt::Display for DeckCard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id)?;
+        if self.upgrades > 0 {
+            write!(f, "+{}", self.upgrades)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for OwnedRelic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.counter {
+            Some(counter) if counter >= 0 => write!(f, "{} ({})", self.id, counter),
+            _ => write!(f, "{}", self.id),
+        }
+    }
+}
+
+fn join<T: ToString>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl std::fmt::Display for SaveStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(character) = &self.character {
+            writeln!(f, "Character: {}", character)?;
+        }
+        writeln!(f, "HP: {}/{}", self.current_health, self.max_health)?;
+        match self.gold_gained {
+            Some(gold_gained) => writeln!(f, "Gold: {} ({} gained)", self.gold, gold_gained)?,
+            None => writeln!(f, "Gold: {}", self.gold)?,
+        }
+        if let (Some(floor), Some(act)) = (self.floor, self.act) {
+            writeln!(f, "Floor: {} (act {})", floor, act)?;
+        }
+        writeln!(f, "Cards ({}): {}", self.deck.len(), join(&self.deck))?;
+        writeln!(f, "Relics ({}): {}", self.relics.len(), join(&self.relics))?;
+        match self.potion_slots {
+            Some(slots) => writeln!(f, "Potions ({} slots): {}", slots, join(&self.potions)),
+            None => writeln!(f, "Potions: {}", join(&self.potions)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn test_status() -> SaveStatus {
+        let save = SaveFile::from_json(json!({
+            "loadout": "IRONCLAD",
+            "current_health": 60,
+            "max_health": 80,
+            "gold": 99,
+            "gold_gained": 12,
+            "cards": [
+                {"id": "Strike_R", "upgrades": 0, "misc": 0},
+                {"id": "Genetic Algorithm", "upgrades": 1, "misc": 7}
+            ],
+            "relics": ["Burning Blood", "Pen Nib"],
+            "relic_counters": [-1],
+            "potions": ["Fire Potion", "Potion Slot"],
+            "potion_slots": 2,
+            "floor_num": 5,
+            "act_num": 1
+        }))
+        .unwrap();
+        SaveStatus::new(&save)
+    }
+
+    #[test]
+    fn json_fields_are_stable() {
+        let expected = r#"{
+  "schema_version": 1,
+  "character": "IRONCLAD",
+  "gold": 99,
+  "gold_gained": 12,
+  "current_health": 60,
+  "max_health": 80,
+  "floor": 5,
+  "act": 1,
+  "ascension_level": null,
+  "deck": [
+    {
+      "id": "Strike_R",
+      "upgrades": 0,
+      "misc": 0
+    },
+    {
+      "id": "Genetic Algorithm",
+      "upgrades": 1,
+      "misc": 7
+    }
+  ],
+  "relics": [
+    {
+      "id": "Burning Blood",
+      "counter": -1
+    },
+    {
+      "id": "Pen Nib",
+      "counter": null
+    }
+  ],
+  "potions": [
+    "Fire Potion",
+    "Potion Slot"
+  ],
+  "potion_slots": 2
+}"#;
+        assert_eq!(
+            serde_json::to_string_pretty(&test_status()).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn tsv_rows_are_stable() {
+        let expected = "\
+stat\tcharacter\tIRONCLAD
+stat\tgold\t99
+stat\tgold_gained\t12
+stat\tcurrent_health\t60
+stat\tmax_health\t80
+stat\tfloor\t5
+stat\tact\t1
+stat\tpotion_slots\t2
+card\tStrike_R\t0\t0
+card\tGenetic Algorithm\t1\t7
+relic\tBurning Blood\t-1
+relic\tPen Nib\t
+potion\t0\tFire Potion
+potion\t1\tPotion Slot
+";
+        assert_eq!(test_status().to_tsv(), expected);
+    }
+}
@@ -0,0 +1,524 @@
+use anyhow::Result as AnyResult;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use serde_json::Value as JsonValue;
+
+use std::fmt::Debug;
+use std::path::Path;
+
+use crate::cache::STSCache;
+use crate::cache_enums::{CardColor, CardRarity, CardType, RelicTier};
+use crate::codec::SaveFormat;
+use crate::edits::Edit;
+use crate::history::History;
+use crate::save_editor::{self, EditorOptions};
+use crate::save_file::SaveFile;
+use crate::status::SaveStatus;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Pane {
+    Deck,
+    Relics,
+    Potions,
+    Library,
+}
+
+const PANES: [Pane; 4] = [Pane::Deck, Pane::Relics, Pane::Potions, Pane::Library];
+
+/// Which part of `STSCache` the library pane lists.
+#[derive(Clone, Copy, PartialEq)]
+enum LibraryKind {
+    Cards,
+    Relics,
+}
+
+/// A library filter that cycles through "any" and every value present in the cache.
+struct Filter<T> {
+    choices: Vec<T>,
+    selected: Option<usize>,
+}
+
+impl<T: Copy + PartialEq + Debug> Filter<T> {
+    fn new(values: impl Iterator<Item = T>) -> Self {
+        let mut choices = Vec::new();
+        for value in values {
+            if !choices.contains(&value) {
+                choices.push(value);
+            }
+        }
+        Filter {
+            choices,
+            selected: None,
+        }
+    }
+
+    fn next(&mut self) {
+        self.selected = match self.selected {
+            None if !self.choices.is_empty() => Some(0),
+            Some(index) if index + 1 < self.choices.len() => Some(index + 1),
+            _ => None,
+        };
+    }
+
+    fn matches(&self, value: T) -> bool {
+        self.selected.is_none_or(|x| self.choices[x] == value)
+    }
+
+    fn label(&self) -> String {
+        match self.selected {
+            Some(index) => format!("{:?}", self.choices[index]),
+            None => "any".to_owned(),
+        }
+    }
+}
+
+fn matches_search(needle: &str, id: &str, name: &Option<String>) -> bool {
+    std::iter::once(id)
+        .chain(name.as_deref())
+        .any(|x| x.to_lowercase().contains(needle))
+}
+
+fn describe(name: &Option<String>, description: String) -> String {
+    match name {
+        Some(name) => format!("{} - {}", name, description),
+        None => description,
+    }
+}
+
+struct App<'a> {
+    save_file: &'a Path,
+    cache: &'a STSCache,
+    options: &'a EditorOptions,
+    save: SaveFile,
+    format: SaveFormat,
+    /// The save as it is on disk, to tell whether there is anything to write.
+    written: JsonValue,
+    dirty: bool,
+    history: History,
+    focus: Pane,
+    deck: ListState,
+    relics: ListState,
+    potions: ListState,
+    library: ListState,
+    library_kind: LibraryKind,
+    search: String,
+    searching: bool,
+    color: Filter<CardColor>,
+    rarity: Filter<CardRarity>,
+    type_: Filter<CardType>,
+    tier: Filter<RelicTier>,
+    message: String,
+    quit: bool,
+}
+
+impl<'a> App<'a> {
+    fn new(
+        save_file: &'a Path,
+        cache: &'a STSCache,
+        options: &'a EditorOptions,
+    ) -> AnyResult<Self> {
+        let (save, format) = save_editor::load_save(save_file, &options.codec)?;
+        Ok(App {
+            save_file,
+            cache,
+            options,
+            written: save.to_json()?,
+            dirty: false,
+            history: History::new(save.clone()),
+            save,
+            format,
+            focus: Pane::Deck,
+            deck: ListState::default().with_selected(Some(0)),
+            relics: ListState::default().with_selected(Some(0)),
+            potions: ListState::default().with_selected(Some(0)),
+            library: ListState::default().with_selected(Some(0)),
+            library_kind: LibraryKind::Cards,
+            search: String::new(),
+            searching: false,
+            color: Filter::new(cache.cards.iter().map(|x| x.color)),
+            rarity: Filter::new(cache.cards.iter().map(|x| x.rarity)),
+            type_: Filter::new(cache.cards.iter().map(|x| x.type_)),
+            tier: Filter::new(cache.relics.iter().map(|x| x.tier)),
+            message: format!("Loaded {:?}", save_file),
+            quit: false,
+        })
+    }
+
+    /// The ids the library pane shows after search and filters, with a short description.
+    fn library_entries(&self) -> Vec<(&'a str, String)> {
+        let cache = self.cache;
+        let needle = self.search.to_lowercase();
+        match self.library_kind {
+            LibraryKind::Cards => cache
+                .cards
+                .iter()
+                .filter(|x| {
+                    matches_search(&needle, &x.id, &x.name)
+                        && self.color.matches(x.color)
+                        && self.rarity.matches(x.rarity)
+                        && self.type_.matches(x.type_)
+                })
+                .map(|x| {
+                    let description = format!("{:?} {:?} {:?}", x.color, x.rarity, x.type_);
+                    (x.id.as_str(), describe(&x.name, description))
+                })
+                .collect(),
+            LibraryKind::Relics => cache
+                .relics
+                .iter()
+                .filter(|x| matches_search(&needle, &x.id, &x.name) && self.tier.matches(x.tier))
+                .map(|x| (x.id.as_str(), describe(&x.name, format!("{:?}", x.tier))))
+                .collect(),
+        }
+    }
+
+    fn pane_len(&self, pane: Pane) -> usize {
+        match pane {
+            Pane::Deck => self.save.cards.len(),
+            Pane::Relics => self.save.relics.len(),
+            Pane::Potions => self.save.potions.as_ref().map_or(0, |x| x.len()),
+            Pane::Library => self.library_entries().len(),
+        }
+    }
+
+    fn pane_state(&mut self, pane: Pane) -> &mut ListState {
+        match pane {
+            Pane::Deck => &mut self.deck,
+            Pane::Relics => &mut self.relics,
+            Pane::Potions => &mut self.potions,
+            Pane::Library => &mut self.library,
+        }
+    }
+
+    /// Moves the selection of the focused pane, staying within its items.
+    fn move_selection(&mut self, delta: isize) {
+        let len = self.pane_len(self.focus) as isize;
+        let state = self.pane_state(self.focus);
+        let current = state.selected().unwrap_or(0) as isize;
+        state.select(Some((current + delta).clamp(0, (len - 1).max(0)) as usize));
+    }
+
+    /// Keeps every selection on an existing item after the lists changed.
+    fn clamp_selections(&mut self) {
+        for pane in PANES {
+            let len = self.pane_len(pane);
+            let state = self.pane_state(pane);
+            let selected = state.selected().unwrap_or(0);
+            state.select(Some(selected.min(len.saturating_sub(1))));
+        }
+    }
+
+    fn apply(&mut self, edit: Edit) {
+        match edit.apply(&mut self.save, self.cache, &mut rand::thread_rng()) {
+            Ok(()) => {
+                self.message = edit.to_string();
+                self.history.record(edit.to_string(), self.save.clone());
+            }
+            Err(error) => {
+                self.message = format!("Error: {}", error);
+                // Drop whatever a failed edit may have changed.
+                self.save = self.history.current().clone();
+            }
+        }
+    }
+
+    fn write(&mut self) -> AnyResult<()> {
+        if !self.dirty {
+            self.message = "No changes to write.".to_owned();
+            return Ok(());
+        }
+        if self.options.dry_run {
+            self.message = "Dry run, the save was not written.".to_owned();
+            return Ok(());
+        }
+        let value = self.save.to_json()?;
+        let backup_path =
+            save_editor::pack_file(value.clone(), &self.format, self.save_file, self.options)?;
+        self.written = value;
+        self.message = match backup_path {
+            Some(backup_path) => format!("Saved, backed up the save to {:?}", backup_path),
+            None => "Saved.".to_owned(),
+        };
+        Ok(())
+    }
+
+    fn handle_search_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Enter | KeyCode::Esc => self.searching = false,
+            KeyCode::Backspace => {
+                self.search.pop();
+            }
+            KeyCode::Char(c) => self.search.push(c),
+            _ => {}
+        }
+        self.library.select(Some(0));
+    }
+
+    fn handle_deck_key(&mut self, code: KeyCode) {
+        let Some(index) = self.deck.selected().filter(|x| *x < self.save.cards.len()) else {
+            return;
+        };
+        match code {
+            KeyCode::Char('d') | KeyCode::Delete => self.apply(Edit::RemoveCardAt { index }),
+            KeyCode::Char('+') => self.apply(Edit::UpgradeCard { index }),
+            KeyCode::Char('-') => self.apply(Edit::DowngradeCard { index }),
+            KeyCode::Char('*') => self.apply(Edit::UpgradeAllCards),
+            _ => {}
+        }
+    }
+
+    fn handle_relic_key(&mut self, code: KeyCode) {
+        let Some(id) = self.relics.selected().and_then(|x| self.save.relics.get(x)) else {
+            return;
+        };
+        if let KeyCode::Char('d') | KeyCode::Delete = code {
+            let id = id.clone();
+            self.apply(Edit::RemoveRelic { id });
+        }
+    }
+
+    fn handle_potion_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Char('d') | KeyCode::Delete => {
+                if let Some(slot) = self.potions.selected() {
+                    self.apply(Edit::ClearPotions { slot: Some(slot) });
+                }
+            }
+            KeyCode::Char('f') => self.apply(Edit::FillPotions { id: None }),
+            _ => {}
+        }
+    }
+
+    fn handle_library_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Char('/') => self.searching = true,
+            KeyCode::Backspace => self.search.clear(),
+            KeyCode::Char('m') => {
+                self.library_kind = match self.library_kind {
+                    LibraryKind::Cards => LibraryKind::Relics,
+                    LibraryKind::Relics => LibraryKind::Cards,
+                };
+                self.library.select(Some(0));
+            }
+            // Relics have neither a color nor a card rarity to filter on.
+            KeyCode::Char('c') if self.library_kind == LibraryKind::Cards => self.color.next(),
+            KeyCode::Char('r') if self.library_kind == LibraryKind::Cards => self.rarity.next(),
+            KeyCode::Char('t') => match self.library_kind {
+                LibraryKind::Cards => self.type_.next(),
+                LibraryKind::Relics => self.tier.next(),
+            },
+            KeyCode::Enter | KeyCode::Char('a') | KeyCode::Char('+') => {
+                let entries = self.library_entries();
+                let Some((id, _)) = self.library.selected().and_then(|x| entries.get(x)) else {
+                    return;
+                };
+                let id = id.to_string();
+                let edit = match self.library_kind {
+                    LibraryKind::Cards => Edit::AddCard {
+                        id,
+                        upgrades: if code == KeyCode::Char('+') { 1 } else { 0 },
+                        count: 1,
+                    },
+                    LibraryKind::Relics => Edit::AddRelic { id },
+                };
+                self.apply(edit);
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_key(&mut self, code: KeyCode) {
+        if self.searching {
+            self.handle_search_key(code);
+            return;
+        }
+        let focus_index = PANES.iter().position(|x| *x == self.focus).unwrap_or(0);
+        match code {
+            KeyCode::Char('q') if self.dirty => {
+                self.message =
+                    "Unsaved changes: 'w' writes them, 'Q' quits without saving.".to_owned();
+            }
+            KeyCode::Char('q') | KeyCode::Char('Q') => self.quit = true,
+            KeyCode::Char('w') => {
+                if let Err(error) = self.write() {
+                    self.message = format!("Error: {:#}", error);
+                }
+            }
+            KeyCode::Char('u') => match self.history.undo() {
+                Some(previous) => self.save = previous.clone(),
+                None => self.message = "Nothing to undo.".to_owned(),
+            },
+            KeyCode::Char('U') => match self.history.redo() {
+                Some(next) => self.save = next.clone(),
+                None => self.message = "Nothing to redo.".to_owned(),
+            },
+            KeyCode::Tab => self.focus = PANES[(focus_index + 1) % PANES.len()],
+            KeyCode::BackTab => self.focus = PANES[(focus_index + PANES.len() - 1) % PANES.len()],
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::PageUp => self.move_selection(-10),
+            KeyCode::PageDown => self.move_selection(10),
+            _ => match self.focus {
+                Pane::Deck => self.handle_deck_key(code),
+                Pane::Relics => self.handle_relic_key(code),
+                Pane::Library => self.handle_library_key(code),
+                Pane::Potions => self.handle_potion_key(code),
+            },
+        }
+        self.clamp_selections();
+    }
+
+    fn help(&self) -> &'static str {
+        if self.searching {
+            return "Type to search, Enter/Esc to stop searching";
+        }
+        match self.focus {
+            Pane::Deck => {
+                "d remove  +/- upgrade/downgrade  * upgrade all  |  Tab pane  u/U undo/redo  w write  q quit  Q discard"
+            }
+            Pane::Library => match self.library_kind {
+                LibraryKind::Cards => {
+                    "/ search  a add  + add upgraded  m cards/relics  c/r/t filters  |  Tab pane  w write  q quit  Q discard"
+                }
+                LibraryKind::Relics => {
+                    "/ search  a add  m cards/relics  t tier filter  |  Tab pane  w write  q quit  Q discard"
+                }
+            },
+            Pane::Relics => "d remove  |  Tab pane  u/U undo/redo  w write  q quit  Q discard",
+            Pane::Potions => {
+                "d empty slot  f fill with random potions  |  Tab pane  u/U undo/redo  w write  q quit  Q discard"
+            }
+        }
+    }
+
+    fn block(&self, pane: Pane, title: String) -> Block<'static> {
+        let block = Block::bordered().title(title);
+        if self.focus == pane {
+            block.border_style(Style::new().yellow())
+        } else {
+            block
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let status = SaveStatus::new(&self.save);
+        let [main, help, message] = Layout::vertical([
+            Constraint::Min(0),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [left, deck, library] = Layout::horizontal([
+            Constraint::Percentage(30),
+            Constraint::Percentage(30),
+            Constraint::Percentage(40),
+        ])
+        .areas(main);
+        let [stats, relics, potions] = Layout::vertical([
+            Constraint::Length(8),
+            Constraint::Min(3),
+            Constraint::Length(status.potions.len() as u16 + 2),
+        ])
+        .areas(left);
+
+        let mut stats_lines = vec![
+            Line::from(format!(
+                "Character: {}",
+                status.character.as_deref().unwrap_or("?")
+            )),
+            Line::from(format!(
+                "HP: {}/{}",
+                status.current_health, status.max_health
+            )),
+            Line::from(format!("Gold: {}", status.gold)),
+        ];
+        if let (Some(floor), Some(act)) = (status.floor, status.act) {
+            stats_lines.push(Line::from(format!("Floor: {} (act {})", floor, act)));
+        }
+        if let Some(ascension_level) = status.ascension_level {
+            stats_lines.push(Line::from(format!("Ascension: {}", ascension_level)));
+        }
+        if self.dirty {
+            stats_lines.push(Line::from("Unsaved changes".bold()));
+        }
+        frame.render_widget(
+            Paragraph::new(stats_lines).block(Block::bordered().title("Stats")),
+            stats,
+        );
+
+        let highlight = Style::new().reversed();
+        let deck_list = List::new(status.deck.iter().map(|x| x.to_string()))
+            .block(self.block(Pane::Deck, format!("Deck ({})", status.deck.len())))
+            .highlight_style(highlight);
+        frame.render_stateful_widget(deck_list, deck, &mut self.deck);
+
+        let relic_list = List::new(status.relics.iter().map(|x| x.to_string()))
+            .block(self.block(Pane::Relics, format!("Relics ({})", status.relics.len())))
+            .highlight_style(highlight);
+        frame.render_stateful_widget(relic_list, relics, &mut self.relics);
+
+        let potions_title = match status.potion_slots {
+            Some(slots) => format!("Potions ({} slots)", slots),
+            None => "Potions".to_owned(),
+        };
+        let potion_list = List::new(status.potions.clone())
+            .block(self.block(Pane::Potions, potions_title))
+            .highlight_style(highlight);
+        frame.render_stateful_widget(potion_list, potions, &mut self.potions);
+
+        let library_title = match self.library_kind {
+            LibraryKind::Cards => format!(
+                "Cards [color: {}, rarity: {}, type: {}]",
+                self.color.label(),
+                self.rarity.label(),
+                self.type_.label()
+            ),
+            LibraryKind::Relics => format!("Relics [tier: {}]", self.tier.label()),
+        };
+        let search = if self.searching {
+            format!("/{}_", self.search)
+        } else {
+            format!("/{}", self.search)
+        };
+        let library_list = List::new(
+            self.library_entries()
+                .into_iter()
+                .map(|(id, description)| format!("{:<28} {}", id, description)),
+        )
+        .block(
+            self.block(Pane::Library, library_title)
+                .title_bottom(Line::from(search)),
+        )
+        .highlight_style(highlight);
+        frame.render_stateful_widget(library_list, library, &mut self.library);
+
+        frame.render_widget(Paragraph::new(self.help().dim()), help);
+        frame.render_widget(Paragraph::new(self.message.as_str()), message);
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> AnyResult<()> {
+        while !self.quit {
+            self.dirty = self.save.to_json()? != self.written;
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    self.handle_key(key.code);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Opens the save in a full-screen editor until the user quits.
+pub fn run(save_file: &Path, cache: &STSCache, options: &EditorOptions) -> AnyResult<()> {
+    let mut app = App::new(save_file, cache, options)?;
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result
+}